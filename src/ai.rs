@@ -1,32 +1,185 @@
+use serde::{Deserialize, Serialize};
 use tcod::Map;
-use crate::{Game, mut_two, PLAYER_ID};
-use crate::object::{move_by, Object};
+use crate::{Game, PLAYER_ID};
+use crate::combat::attack;
+use crate::ecs::{Entity, World};
+use crate::effects::{has_effect, EffectKind};
+use crate::gamemap::is_blocked;
+use crate::movement::{distance, move_by, stumble};
+use crate::pathfinding::find_path;
+use crate::ranged::{clear_shot, shoot, RangedAttack};
+use crate::scheduler::Action;
+use crate::traps::known_trap_at;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Ranged monsters back off when the player gets closer than this or half their range, whichever is further.
+const KEEP_DISTANCE: i32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Ai {
     Basic,
+    /// Keeps its distance from the player and shoots its `RangedAttack`.
+    Ranged,
+    /// Heard an alarm and heads for it, then goes back to `previous_ai`.
+    Alerted {
+        x: i32,
+        y: i32,
+        num_turns: i32,
+        previous_ai: Box<Ai>,
+    },
 }
 
-fn move_towards(id: usize, target_x: i32, target_y: i32, game: &Game, objects: &mut [Object]) {
-    let (x, y) = objects[id].position();
-    let dx = target_x - x;
-    let dy = target_y - y;
-    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
+/// Steps along the cheapest path to the target, or straight at it when no path is found.
+fn move_towards(entity: Entity, target_x: i32, target_y: i32, game: &mut Game, world: &mut World) -> Action {
+    let (x, y) = match world.position(entity) {
+        Some(position) => position,
+        None => return Action::Wait,
+    };
+    if let Some(path) = find_path((x, y), (target_x, target_y), entity, &game.map, world) {
+        if let Some(&(next_x, next_y)) = path.first() {
+            return move_by(entity, next_x - x, next_y - y, game, world);
+        }
+    }
 
-    let dx = (dx as f32 / distance).round() as i32;
-    let dy = (dy as f32 / distance).round() as i32;
-    move_by(id, dx, dy, game, objects);
+    let dx = (target_x - x).signum();
+    let dy = (target_y - y).signum();
+    move_by(entity, dx, dy, game, world)
 }
 
-pub fn ai_take_turn(monster_id: usize, fov_map: &Map, game: &mut Game, objects: &mut [Object]) {
-    let (monster_x, monster_y) = objects[monster_id].position();
+/// Lets the monster act and returns what it did, so the scheduler can charge for it. Paralysed monsters wait and
+/// confused ones stumble around, whatever their ai.
+pub fn ai_take_turn(monster: Entity, fov_map: &Map, game: &mut Game, world: &mut World) -> Action {
+    use Ai::*;
+    if has_effect(monster, EffectKind::Paralysed, world) {
+        return Action::Wait;
+    }
+    if has_effect(monster, EffectKind::Confused, world) {
+        return stumble(monster, game, world);
+    }
+    match world.remove::<Ai>(monster) {
+        Some(ai) => {
+            let (new_ai, action) = match ai {
+                Basic => ai_basic(monster, fov_map, game, world),
+                Ranged => ai_ranged(monster, fov_map, game, world),
+                Alerted { x, y, num_turns, previous_ai } => {
+                    ai_alerted(monster, fov_map, game, world, (x, y), num_turns, *previous_ai)
+                }
+            };
+            // a trap may have killed it on the way, the corpse keeps no ai
+            if world.fighters.contains(monster) {
+                world.insert(monster, new_ai);
+            }
+            action
+        }
+        None => Action::Wait,
+    }
+}
+
+fn ai_basic(monster: Entity, fov_map: &Map, game: &mut Game, world: &mut World) -> (Ai, Action) {
+    let (monster_x, monster_y) = match world.position(monster) {
+        Some(position) => position,
+        None => return (Ai::Basic, Action::Wait),
+    };
     if fov_map.is_in_fov(monster_x, monster_y) {
-        if objects[monster_id].distance_to(&objects[PLAYER_ID]) >= 2.0 {
-            let (px, py) = objects[PLAYER_ID].position();
-            move_towards(monster_id, px, py, game, objects);
+        let (px, py) = world.player_position();
+        if distance((monster_x, monster_y), (px, py)) >= 2.0 {
+            (Ai::Basic, move_towards(monster, px, py, game, world))
         } else {
-            let (player, target) = mut_two(monster_id, PLAYER_ID, objects);
-            player.attack(target, game);
+            attack(monster, PLAYER_ID, game, world);
+            (Ai::Basic, Action::Attack)
+        }
+    } else {
+        (Ai::Basic, Action::Wait)
+    }
+}
+
+/// Backs off when the player comes next to it, shoots when the player is in range and nothing is in the way, and
+/// otherwise closes in. Cornered, it fights hand to hand.
+fn ai_ranged(monster: Entity, fov_map: &Map, game: &mut Game, world: &mut World) -> (Ai, Action) {
+    let position = match world.position(monster) {
+        Some(position) => position,
+        None => return (Ai::Ranged, Action::Wait),
+    };
+    if !fov_map.is_in_fov(position.0, position.1) {
+        return (Ai::Ranged, Action::Wait);
+    }
+    let player = world.player_position();
+    let distance_to_player = distance(position, player);
+    let range = world.get::<RangedAttack>(monster).map_or(0, |attack| attack.range);
+    let keep_distance = (range / 2).max(KEEP_DISTANCE).min(range) as f32;
+    if distance_to_player < keep_distance {
+        if let Some((dx, dy)) = retreat(monster, position, player, game, world) {
+            return (Ai::Ranged, move_by(monster, dx, dy, game, world));
+        }
+    }
+
+    if distance_to_player < 2.0 {
+        attack(monster, PLAYER_ID, game, world);
+        (Ai::Ranged, Action::Attack)
+    } else if distance_to_player > range as f32 {
+        (Ai::Ranged, move_towards(monster, player.0, player.1, game, world))
+    } else if clear_shot(position, PLAYER_ID, &game.map, world) {
+        shoot(monster, player, game, world);
+        (Ai::Ranged, Action::Attack)
+    } else {
+        // something is in the way: step aside rather than walk up to the player
+        let floor = distance_to_player.min(keep_distance);
+        match sidestep(monster, position, player, floor, range as f32, game, world) {
+            Some((dx, dy)) => (Ai::Ranged, move_by(monster, dx, dy, game, world)),
+            None => (Ai::Ranged, Action::Wait),
         }
     }
 }
+
+/// The neighbouring tiles the monster can step onto without walking into a known trap.
+fn free_steps(monster: Entity, (x, y): (i32, i32), game: &Game, world: &World) -> Vec<(i32, i32)> {
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&(dx, dy)| {
+            (dx, dy) != (0, 0)
+                && !is_blocked(x + dx, y + dy, monster, &game.map, world)
+                && known_trap_at(x + dx, y + dy, world).is_none()
+        })
+        .collect()
+}
+
+/// The step that takes the monster furthest from the player, if any gets it further away.
+fn retreat(monster: Entity, (x, y): (i32, i32), player: (i32, i32), game: &Game, world: &World) -> Option<(i32, i32)> {
+    let current = distance((x, y), player);
+    free_steps(monster, (x, y), game, world)
+        .into_iter()
+        .map(|(dx, dy)| ((dx, dy), distance((x + dx, y + dy), player)))
+        .filter(|&(_, further)| further > current)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(step, _)| step)
+}
+
+/// A step that stays between `min` and `max` from the player, one that opens a clear shot if there is any, else
+/// the one that changes the distance the least.
+fn sidestep(monster: Entity, (x, y): (i32, i32), player: (i32, i32), min: f32, max: f32, game: &Game, world: &World) -> Option<(i32, i32)> {
+    let current = distance((x, y), player);
+    let steps: Vec<((i32, i32), f32)> = free_steps(monster, (x, y), game, world)
+        .into_iter()
+        .map(|(dx, dy)| ((dx, dy), distance((x + dx, y + dy), player)))
+        .filter(|&(_, to_player)| to_player >= min && to_player <= max)
+        .collect();
+    steps.iter()
+        .find(|&&((dx, dy), _)| clear_shot((x + dx, y + dy), PLAYER_ID, &game.map, world))
+        .or_else(|| steps.iter().min_by(|a, b| (a.1 - current).abs().total_cmp(&(b.1 - current).abs())))
+        .map(|&(step, _)| step)
+}
+
+/// Goes to the alarm until the player comes into view, the alarm is reached or the monster loses interest.
+fn ai_alerted(monster: Entity, fov_map: &Map, game: &mut Game, world: &mut World, (x, y): (i32, i32), num_turns: i32, previous_ai: Ai) -> (Ai, Action) {
+    let position = match world.position(monster) {
+        Some(position) => position,
+        None => return (previous_ai, Action::Wait),
+    };
+    if fov_map.is_in_fov(position.0, position.1) || position == (x, y) || num_turns <= 0 {
+        return match previous_ai {
+            Ai::Ranged => ai_ranged(monster, fov_map, game, world),
+            _ => ai_basic(monster, fov_map, game, world),
+        };
+    }
+    let action = move_towards(monster, x, y, game, world);
+    (Ai::Alerted { x, y, num_turns: num_turns - 1, previous_ai: Box::new(previous_ai) }, action)
+}
@@ -0,0 +1,158 @@
+//! Backend-agnostic drawing and input.
+//!
+//! The game only talks to a `Renderer` (a grid of character cells) and an `Input` (a stream of key and mouse
+//! events). `tcod_backend` drives a real window, `headless` keeps everything in memory.
+//! Colours and text alignment are plain tcod value types and need no window.
+
+use tcod::{Color, TextAlignment};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    #[default]
+    None,
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    /// Numeric keypad digit.
+    NumPad(u8),
+    Enter,
+    Escape,
+    Backspace,
+    Spacebar,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl Key {
+    pub fn new(code: KeyCode) -> Self {
+        Self { code, ..Default::default() }
+    }
+
+    pub fn char(c: char) -> Self {
+        Self::new(KeyCode::Char(c))
+    }
+}
+
+/// Mouse state, in character cells.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mouse {
+    pub x: i32,
+    pub y: i32,
+    pub left_clicked: bool,
+    pub right_clicked: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Key(Key),
+    Mouse(Mouse),
+}
+
+pub trait Renderer {
+    fn size(&self) -> (i32, i32);
+
+    fn clear(&mut self);
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: Color);
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color);
+
+    /// Shows everything drawn since the last call.
+    fn flush(&mut self);
+
+    fn toggle_fullscreen(&mut self) {}
+
+    fn window_closed(&self) -> bool {
+        false
+    }
+
+    fn print(&mut self, x: i32, y: i32, text: &str, color: Color, alignment: TextAlignment) {
+        let len = text.chars().count() as i32;
+        let start_x = match alignment {
+            TextAlignment::Left => x,
+            TextAlignment::Center => x - len / 2,
+            TextAlignment::Right => x - len + 1,
+        };
+        for (i, glyph) in text.chars().enumerate() {
+            self.put_char(start_x + i as i32, y, glyph, color);
+        }
+    }
+
+    /// Prints word-wrapped text and returns the number of lines used.
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, text: &str, color: Color) -> i32 {
+        let lines = wrap_text(text, width);
+        for (i, line) in lines.iter().enumerate() {
+            self.print(x, y + i as i32, line, color, TextAlignment::Left);
+        }
+        lines.len() as i32
+    }
+
+    fn fill_background(&mut self, x: i32, y: i32, width: i32, height: i32, color: Color) {
+        for cell_y in y..y + height {
+            for cell_x in x..x + width {
+                self.set_background(cell_x, cell_y, color);
+            }
+        }
+    }
+}
+
+pub trait Input {
+    /// Returns the next pending event without waiting for one.
+    fn check_for_event(&mut self) -> Option<Event>;
+
+    /// Blocks until a key is pressed.
+    fn wait_for_keypress(&mut self) -> Key;
+
+    /// Whether no more input will ever come, like a headless script played to its end.
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// Number of lines `print_rect` needs for `text`.
+pub fn text_height(text: &str, width: i32) -> i32 {
+    wrap_text(text, width).len() as i32
+}
+
+/// Splits text into lines of at most `width` characters, breaking at spaces where possible.
+pub fn wrap_text(text: &str, width: i32) -> Vec<String> {
+    let width = width.max(1) as usize;
+    let mut lines = vec![];
+    if text.is_empty() {
+        return lines;
+    }
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let line_len = line.chars().count();
+            if line_len > 0 && line_len + 1 + word.chars().count() > width {
+                lines.push(line);
+                line = String::new();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            while line.chars().count() > width {
+                let rest = line.chars().skip(width).collect();
+                line = line.chars().take(width).collect();
+                lines.push(line);
+                line = rest;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
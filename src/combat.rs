@@ -0,0 +1,129 @@
+use tcod::colors::{DARK_RED, ORANGE, RED, WHITE};
+use crate::{Game, PLAYER_ID};
+use crate::components::{BlocksMovement, DeathCallback, Equipment, Fighter, Name};
+use crate::ai::Ai;
+use crate::ecs::{Entity, World};
+use crate::effects::{apply_on_hit, has_effect, EffectKind, StatusEffects};
+use crate::scheduler::Actor;
+
+pub const LEVEL_UP_BASE: i32 = 200;
+pub const LEVEL_UP_FACTOR: i32 = 150;
+
+/// Experience needed to advance from `level` to the next one.
+pub fn level_up_xp(level: i32) -> i32 {
+    LEVEL_UP_BASE + level * LEVEL_UP_FACTOR
+}
+
+impl DeathCallback {
+    fn callback(self, entity: Entity, game: &mut Game, world: &mut World) {
+        use DeathCallback::*;
+        let callback: fn(Entity, &mut Game, &mut World) = match self {
+            Player => player_death,
+            Monster => monster_death,
+        };
+        callback(entity, game, world);
+    }
+}
+
+fn player_death(player: Entity, game: &mut Game, world: &mut World) {
+    game.messages.add("You died!", RED);
+
+    if let Some(renderable) = world.renderables.get_mut(player) {
+        renderable.glyph = '%';
+        renderable.color = DARK_RED;
+    }
+}
+
+fn monster_death(monster: Entity, game: &mut Game, world: &mut World) {
+    game.messages.add(format!("{} is dead!", world.name(monster)), ORANGE);
+    if let Some(renderable) = world.renderables.get_mut(monster) {
+        renderable.glyph = '%';
+        renderable.color = DARK_RED;
+    }
+    world.remove::<BlocksMovement>(monster);
+    world.remove::<Fighter>(monster);
+    world.remove::<Ai>(monster);
+    world.remove::<Actor>(monster);
+    world.remove::<StatusEffects>(monster);
+    let name = format!("remains of {}", world.name(monster));
+    world.insert(monster, Name(name));
+}
+
+/// Returns the experience the entity was worth if the damage killed it.
+pub fn take_damage(target: Entity, damage: i32, game: &mut Game, world: &mut World) -> Option<i32> {
+    let fighter = world.fighters.get_mut(target)?;
+    if damage > 0 {
+        fighter.hp -= damage;
+    }
+
+    let fighter = *fighter;
+    if fighter.hp <= 0 {
+        fighter.on_death.callback(target, game, world);
+        return Some(fighter.xp);
+    }
+    None
+}
+
+pub fn attack(attacker: Entity, target: Entity, game: &mut Game, world: &mut World) {
+    let mut damage = power(attacker, game, world) - defense(target, game, world);
+    // swinging blindly
+    if has_effect(attacker, EffectKind::Blinded, world) {
+        damage /= 2;
+    }
+
+    if damage > 0 {
+        game.messages.add(format!("{} attacks {} for {} hit points.", world.name(attacker), world.name(target), damage), WHITE);
+        if let Some(xp) = take_damage(target, damage, game, world) {
+            gain_xp(attacker, xp, world);
+        }
+        apply_on_hit(attacker, target, game, world);
+    } else {
+        game.messages.add(format!("{} attacks {}, but it has no effect!", world.name(attacker), world.name(target)), WHITE);
+    }
+}
+
+pub fn gain_xp(entity: Entity, xp: i32, world: &mut World) {
+    if let Some(fighter) = world.fighters.get_mut(entity) {
+        fighter.xp += xp;
+    }
+}
+
+pub fn heal(entity: Entity, amount: i32, game: &Game, world: &mut World) {
+    let max_hp = max_hp(entity, game, world);
+    if let Some(fighter) = world.fighters.get_mut(entity) {
+        fighter.hp += amount;
+        if fighter.hp > max_hp {
+            fighter.hp = max_hp;
+        }
+    }
+}
+
+pub fn power(entity: Entity, game: &Game, world: &World) -> i32 {
+    let base_power = world.fighters.get(entity).map_or(0, |f| f.power);
+    let bonus: i32 = get_all_equipped(entity, game, world).iter().map(|e| e.power_bonus).sum();
+    base_power + bonus
+}
+
+pub fn defense(entity: Entity, game: &Game, world: &World) -> i32 {
+    let base_defense = world.fighters.get(entity).map_or(0, |f| f.defense);
+    let bonus: i32 = get_all_equipped(entity, game, world).iter().map(|e| e.defense_bonus).sum();
+    base_defense + bonus
+}
+
+pub fn max_hp(entity: Entity, game: &Game, world: &World) -> i32 {
+    let base_max_hp = world.fighters.get(entity).map_or(0, |f| f.max_hp);
+    let bonus: i32 = get_all_equipped(entity, game, world).iter().map(|e| e.max_hp_bonus).sum();
+    base_max_hp + bonus
+}
+
+/// Only the player carries an inventory, so everybody else fights with bare stats.
+fn get_all_equipped(entity: Entity, game: &Game, world: &World) -> Vec<Equipment> {
+    if entity == PLAYER_ID {
+        game.inventory.iter()
+            .filter_map(|&item| world.equipment.get(item).copied())
+            .filter(|equipment| equipment.equipped)
+            .collect()
+    } else {
+        vec![]
+    }
+}
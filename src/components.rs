@@ -0,0 +1,154 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use tcod::Color;
+use crate::PLAYER_ID;
+use crate::ecs::{Entity, World};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Renderable {
+    pub glyph: char,
+    pub color: Color,
+    /// Drawn on explored tiles even when out of sight, like stairs.
+    pub always_visible: bool,
+}
+
+impl Renderable {
+    pub fn new(glyph: char, color: Color) -> Self {
+        Self { glyph, color, always_visible: false }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Name(pub String);
+
+impl Name {
+    pub fn new(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+/// Nothing else can step onto the entity's tile.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlocksMovement;
+
+/// Taking these leads to the next dungeon level.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stairs;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fighter {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    /// For monsters the experience awarded for killing them, for the player the experience gathered so far.
+    pub xp: i32,
+    pub on_death: DeathCallback,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+/// Character level, only the player gains levels.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Level(pub i32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    pub max_hp_bonus: i32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Slot {
+    MainHand,
+    OffHand,
+    Head,
+    Body,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Slot::MainHand => write!(f, "main hand"),
+            Slot::OffHand => write!(f, "off hand"),
+            Slot::Head => write!(f, "head"),
+            Slot::Body => write!(f, "body"),
+        }
+    }
+}
+
+/// How a creature gets around, creatures without one walk.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locomotion {
+    #[default]
+    Walk,
+    /// Also crosses deep water.
+    Swim,
+    /// Crosses water, lava and chasms unharmed.
+    Fly,
+}
+
+/// How many identical items a stack holds, items without one are single.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quantity(pub u32);
+
+/// Shatters when thrown, and the creature it breaks over gets what drinking it would do.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Potion;
+
+/// Shortcuts for the components nearly everything asks about.
+impl World {
+    pub fn position(&self, entity: Entity) -> Option<(i32, i32)> {
+        self.positions.get(entity).map(|p| (p.x, p.y))
+    }
+
+    /// The player always stands somewhere on the map.
+    pub fn player_position(&self) -> (i32, i32) {
+        self.position(PLAYER_ID).expect("the player has no position")
+    }
+
+    pub fn set_position(&mut self, entity: Entity, x: i32, y: i32) {
+        self.insert(entity, Position::new(x, y));
+    }
+
+    pub fn name(&self, entity: Entity) -> &str {
+        self.names.get(entity).map_or("something", |name| &name.0)
+    }
+
+    pub fn quantity(&self, entity: Entity) -> u32 {
+        self.quantities.get(entity).map_or(1, |q| q.0)
+    }
+
+    /// Fighters stay alive until their hit points run out, everything else never was.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.fighters.get(entity).is_some_and(|f| f.hp > 0)
+    }
+
+    /// Entities standing on a tile.
+    pub fn entities_at(&self, x: i32, y: i32) -> impl Iterator<Item = Entity> + '_ {
+        self.positions.iter()
+            .filter(move |(_, p)| p.x == x && p.y == y)
+            .map(|(entity, _)| entity)
+    }
+}
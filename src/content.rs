@@ -0,0 +1,512 @@
+//! Monster, item and vault templates, read from the TOML files in `data/` at startup.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use serde::Deserialize;
+use tcod::Color;
+use crate::ai::Ai;
+use crate::components::{BlocksMovement, DeathCallback, Equipment, Fighter, Locomotion, Name, Position, Potion, Quantity, Renderable, Slot};
+use crate::ecs::{Entity, World};
+use crate::effects::OnHit;
+use crate::gamemap::{Transition, MAP_HEIGHT, MAP_WIDTH};
+use crate::inventory::Item;
+use crate::ranged::{Launcher, RangedAttack};
+use crate::scheduler::{Actor, NORMAL_SPEED};
+
+pub const MONSTERS_FILE: &str = "data/monsters.toml";
+pub const ITEMS_FILE: &str = "data/items.toml";
+pub const VAULTS_FILE: &str = "data/vaults.toml";
+
+const BUILTIN_MONSTERS: &str = include_str!("../data/monsters.toml");
+const BUILTIN_ITEMS: &str = include_str!("../data/items.toml");
+const BUILTIN_VAULTS: &str = include_str!("../data/vaults.toml");
+
+/// Characters with a fixed meaning in vault maps, everything else is looked up in the vault's legend.
+pub const VAULT_WALL: char = '#';
+pub const VAULT_FLOOR: char = '.';
+pub const VAULT_DOOR: char = '+';
+pub const VAULT_SECRET_DOOR: char = '=';
+/// Not part of the vault, the level is left as it is.
+pub const VAULT_OUTSIDE: char = ' ';
+
+#[derive(Clone, Debug)]
+pub struct Content {
+    pub monsters: Vec<MonsterTemplate>,
+    pub items: Vec<ItemTemplate>,
+    pub vaults: Vec<VaultTemplate>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: [u8; 3],
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    pub xp: i32,
+    #[serde(default)]
+    pub ai: AiKind,
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    #[serde(default)]
+    pub locomotion: Locomotion,
+    /// Status effect its hits may cause.
+    pub on_hit: Option<OnHit>,
+    /// What a monster with the "ranged" ai shoots.
+    pub ranged: Option<RangedAttack>,
+    pub spawn: Vec<Transition>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiKind {
+    #[default]
+    Basic,
+    Ranged,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: [u8; 3],
+    pub effect: Item,
+    pub equipment: Option<EquipmentTemplate>,
+    /// Makes the equipment a bow or sling shooting the named ammunition.
+    pub launcher: Option<Launcher>,
+    /// How many are found together, in one stack.
+    #[serde(default = "one")]
+    pub quantity: u32,
+    /// Shatters when thrown.
+    #[serde(default)]
+    pub potion: bool,
+    pub spawn: Vec<Transition>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EquipmentTemplate {
+    pub slot: Slot,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+    #[serde(default)]
+    pub max_hp_bonus: i32,
+}
+
+/// A hand-made room stamped into generated levels.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultTemplate {
+    pub name: String,
+    pub map: String,
+    /// Monster or item names spawned on the tiles marked with the character.
+    #[serde(default)]
+    pub legend: HashMap<char, String>,
+    /// Whether the vault may be turned by a quarter, half or three quarters.
+    #[serde(default)]
+    pub rotate: bool,
+    /// Whether the vault may be flipped left to right.
+    #[serde(default)]
+    pub mirror: bool,
+    pub spawn: Vec<Transition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonstersFile {
+    #[serde(default, rename = "monster")]
+    monsters: Vec<MonsterTemplate>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemsFile {
+    #[serde(default, rename = "item")]
+    items: Vec<ItemTemplate>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VaultsFile {
+    #[serde(default, rename = "vault")]
+    vaults: Vec<VaultTemplate>,
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Default for Content {
+    /// The templates built into the game.
+    fn default() -> Self {
+        let monsters = parse_monsters(MONSTERS_FILE, BUILTIN_MONSTERS).unwrap_or_else(|e| panic!("{}", e));
+        let items = parse_items(ITEMS_FILE, BUILTIN_ITEMS).unwrap_or_else(|e| panic!("{}", e));
+        let vaults = parse_vaults(VAULTS_FILE, BUILTIN_VAULTS, &monsters, &items).unwrap_or_else(|e| panic!("{}", e));
+        Content { monsters, items, vaults }
+    }
+}
+
+impl Content {
+    /// Reads the monster, item and vault files, a missing file falls back to the built-in templates.
+    pub fn load(monsters_path: &str, items_path: &str, vaults_path: &str) -> Result<Content, ContentError> {
+        let monsters = parse_monsters(monsters_path, &read_or(monsters_path, BUILTIN_MONSTERS)?)?;
+        let items = parse_items(items_path, &read_or(items_path, BUILTIN_ITEMS)?)?;
+        let vaults = parse_vaults(vaults_path, &read_or(vaults_path, BUILTIN_VAULTS)?, &monsters, &items)?;
+        Ok(Content { monsters, items, vaults })
+    }
+
+    /// Spawns the monster or item with the name, monsters first.
+    pub fn spawn(&self, name: &str, x: i32, y: i32, world: &mut World) -> Option<Entity> {
+        if let Some(monster) = self.monsters.iter().find(|monster| monster.name == name) {
+            return Some(monster.spawn(x, y, world));
+        }
+        let item = self.items.iter().find(|item| item.name == name)?;
+        Some(item.spawn(x, y, world))
+    }
+}
+
+impl MonsterTemplate {
+    pub fn spawn(&self, x: i32, y: i32, world: &mut World) -> Entity {
+        let ai = match self.ai {
+            AiKind::Basic => Ai::Basic,
+            AiKind::Ranged => Ai::Ranged,
+        };
+        let monster = world.spawn()
+            .with(Position::new(x, y))
+            .with(Renderable::new(self.glyph, to_color(self.color)))
+            .with(Name::new(&self.name))
+            .with(BlocksMovement)
+            .with(Fighter {
+                max_hp: self.hp,
+                hp: self.hp,
+                defense: self.defense,
+                power: self.power,
+                xp: self.xp,
+                on_death: DeathCallback::Monster,
+            })
+            .with(ai)
+            .with(Actor::new(self.speed))
+            .with(self.locomotion)
+            .build();
+        if let Some(on_hit) = self.on_hit {
+            world.insert(monster, on_hit);
+        }
+        if let Some(ranged) = &self.ranged {
+            world.insert(monster, ranged.clone());
+        }
+        monster
+    }
+}
+
+impl ItemTemplate {
+    pub fn spawn(&self, x: i32, y: i32, world: &mut World) -> Entity {
+        let item = world.spawn()
+            .with(Position::new(x, y))
+            .with(Renderable::new(self.glyph, to_color(self.color)))
+            .with(Name::new(&self.name))
+            .with(self.effect)
+            .build();
+        if let Some(equipment) = self.equipment {
+            world.insert(item, Equipment {
+                slot: equipment.slot,
+                equipped: false,
+                power_bonus: equipment.power_bonus,
+                defense_bonus: equipment.defense_bonus,
+                max_hp_bonus: equipment.max_hp_bonus,
+            });
+        }
+        if let Some(launcher) = &self.launcher {
+            world.insert(item, launcher.clone());
+        }
+        if self.quantity > 1 {
+            world.insert(item, Quantity(self.quantity));
+        }
+        if self.potion {
+            world.insert(item, Potion);
+        }
+        item
+    }
+}
+
+impl VaultTemplate {
+    /// The map as rows of characters, padded to the same width.
+    pub fn rows(&self) -> Vec<Vec<char>> {
+        let mut rows: Vec<Vec<char>> = self.map.lines().map(|line| line.chars().collect()).collect();
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut rows {
+            row.resize(width, VAULT_OUTSIDE);
+        }
+        rows
+    }
+}
+
+/// Whether the tile at `x`, `y` of the rows is off the vault, so a door next to it opens there.
+pub fn is_outside_vault(rows: &[Vec<char>], x: i32, y: i32) -> bool {
+    if !(0..rows.len() as i32).contains(&y) || !(0..rows[0].len() as i32).contains(&x) {
+        return true;
+    }
+    rows[y as usize][x as usize] == VAULT_OUTSIDE
+}
+
+fn to_color([r, g, b]: [u8; 3]) -> Color {
+    Color { r, g, b }
+}
+
+fn read_or(path: &str, builtin: &str) -> Result<String, ContentError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(builtin.into()),
+        Err(e) => Err(ContentError::new(path, e.to_string())),
+    }
+}
+
+fn parse_monsters(path: &str, text: &str) -> Result<Vec<MonsterTemplate>, ContentError> {
+    let file: MonstersFile = toml::from_str(text).map_err(|e| ContentError::new(path, e.to_string()))?;
+    let mut names = HashSet::new();
+    for monster in &file.monsters {
+        let error = |message: &str| ContentError::new(path, format!("monster \"{}\": {}", monster.name, message));
+        if monster.name.is_empty() {
+            return Err(ContentError::new(path, "a monster has an empty name".into()));
+        }
+        if !names.insert(&monster.name) {
+            return Err(error("defined more than once"));
+        }
+        if monster.hp <= 0 {
+            return Err(error("hp must be above 0"));
+        }
+        if monster.speed <= 0 {
+            return Err(error("speed must be above 0"));
+        }
+        if let Some(on_hit) = monster.on_hit {
+            if on_hit.turns <= 0 {
+                return Err(error("on_hit turns must be above 0"));
+            }
+            if on_hit.chance == 0 || on_hit.chance > 100 {
+                return Err(error("on_hit chance must be between 1 and 100"));
+            }
+        }
+        match (monster.ai, &monster.ranged) {
+            (AiKind::Ranged, None) => return Err(error("ai \"ranged\" needs a `ranged` table")),
+            (AiKind::Basic, Some(_)) => return Err(error("only monsters with ai \"ranged\" can have a `ranged` table")),
+            (AiKind::Ranged, Some(ranged)) if ranged.range <= 0 => return Err(error("ranged range must be above 0")),
+            _ => {}
+        }
+        check_spawn(&monster.spawn).map_err(|message| error(&message))?;
+    }
+    Ok(file.monsters)
+}
+
+fn parse_items(path: &str, text: &str) -> Result<Vec<ItemTemplate>, ContentError> {
+    let file: ItemsFile = toml::from_str(text).map_err(|e| ContentError::new(path, e.to_string()))?;
+    let mut names = HashSet::new();
+    for item in &file.items {
+        let error = |message: &str| ContentError::new(path, format!("item \"{}\": {}", item.name, message));
+        if item.name.is_empty() {
+            return Err(ContentError::new(path, "an item has an empty name".into()));
+        }
+        if !names.insert(&item.name) {
+            return Err(error("defined more than once"));
+        }
+        match (item.effect, item.equipment.is_some()) {
+            (Item::Equip, false) => return Err(error("effect \"equip\" needs an `equipment` table")),
+            (Item::Equip, true) | (_, false) => {}
+            (_, true) => return Err(error("only items with effect \"equip\" can have an `equipment` table")),
+        }
+        if let Some(launcher) = &item.launcher {
+            if item.equipment.is_none_or(|e| e.slot != Slot::MainHand) {
+                return Err(error("a `launcher` must be equipment for the main hand"));
+            }
+            if launcher.range <= 0 {
+                return Err(error("launcher range must be above 0"));
+            }
+            let ammo = file.items.iter().find(|ammo| ammo.name == launcher.ammo);
+            if ammo.is_none_or(|ammo| ammo.effect != Item::Ammo) {
+                return Err(error(&format!("the launcher ammo \"{}\" is no item with effect \"ammo\"", launcher.ammo)));
+            }
+        }
+        if item.potion && !matches!(item.effect, Item::Heal | Item::Haste | Item::Regeneration) {
+            return Err(error("only items with effect \"heal\", \"haste\" or \"regeneration\" can be a potion"));
+        }
+        if item.quantity == 0 {
+            return Err(error("quantity must be above 0"));
+        }
+        if item.quantity > 1 && item.equipment.is_some() {
+            return Err(error("equipment does not stack, its quantity must be 1"));
+        }
+        check_spawn(&item.spawn).map_err(|message| error(&message))?;
+    }
+    Ok(file.items)
+}
+
+fn parse_vaults(path: &str, text: &str, monsters: &[MonsterTemplate], items: &[ItemTemplate]) -> Result<Vec<VaultTemplate>, ContentError> {
+    let file: VaultsFile = toml::from_str(text).map_err(|e| ContentError::new(path, e.to_string()))?;
+    let mut names = HashSet::new();
+    for vault in &file.vaults {
+        let error = |message: &str| ContentError::new(path, format!("vault \"{}\": {}", vault.name, message));
+        if vault.name.is_empty() {
+            return Err(ContentError::new(path, "a vault has an empty name".into()));
+        }
+        if !names.insert(&vault.name) {
+            return Err(error("defined more than once"));
+        }
+
+        for (&symbol, name) in &vault.legend {
+            if [VAULT_WALL, VAULT_FLOOR, VAULT_DOOR, VAULT_SECRET_DOOR, VAULT_OUTSIDE].contains(&symbol) {
+                return Err(error(&format!("'{}' has a fixed meaning and cannot be in the legend", symbol)));
+            }
+            if !monsters.iter().any(|m| &m.name == name) && !items.iter().any(|i| &i.name == name) {
+                return Err(error(&format!("no monster or item is called \"{}\"", name)));
+            }
+        }
+
+        let rows = vault.rows();
+        let (width, height) = (rows.first().map_or(0, Vec::len) as i32, rows.len() as i32);
+        if width == 0 {
+            return Err(error("the map is empty"));
+        }
+        // the vault keeps two tiles from the map edge and may be turned on its side
+        let fits = width <= MAP_WIDTH - 4 && height <= MAP_HEIGHT - 4;
+        let fits_turned = height <= MAP_WIDTH - 4 && width <= MAP_HEIGHT - 4;
+        if !fits || (vault.rotate && !fits_turned) {
+            return Err(error("the map does not fit on a level"));
+        }
+
+        let mut doors = 0;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &symbol) in row.iter().enumerate() {
+                let (x, y) = (x as i32, y as i32);
+                match symbol {
+                    VAULT_WALL | VAULT_FLOOR | VAULT_OUTSIDE => {}
+                    VAULT_DOOR | VAULT_SECRET_DOOR => {
+                        let opens = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter()
+                            .any(|&(nx, ny)| is_outside_vault(&rows, nx, ny));
+                        if !opens {
+                            return Err(error(&format!("the door at line {}, column {} does not lead out", y + 1, x + 1)));
+                        }
+                        doors += 1;
+                    }
+                    symbol if vault.legend.contains_key(&symbol) => {}
+                    symbol => return Err(error(&format!("'{}' is not in the legend", symbol))),
+                }
+            }
+        }
+        if doors == 0 {
+            return Err(error(&format!("the map needs at least one door ('{}')", VAULT_DOOR)));
+        }
+        check_spawn(&vault.spawn).map_err(|message| error(&message))?;
+    }
+    Ok(file.vaults)
+}
+
+fn check_spawn(spawn: &[Transition]) -> Result<(), String> {
+    if spawn.is_empty() {
+        return Err("`spawn` needs at least one entry".into());
+    }
+    if spawn.windows(2).any(|pair| pair[0].level >= pair[1].level) {
+        return Err("`spawn` levels must be increasing".into());
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct ContentError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ContentError {
+    fn new(path: &str, message: String) -> Self {
+        Self { path: path.into(), message }
+    }
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Error for ContentError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORK: &str = r#"
+[[monster]]
+name = "ork"
+glyph = "o"
+color = [63, 127, 63]
+hp = 10
+defense = 0
+power = 3
+xp = 35
+spawn = [{ level = 1, weight = 80 }]
+"#;
+
+    #[test]
+    fn parses_the_bundled_data() {
+        let content = Content::default();
+        assert!(content.monsters.iter().any(|monster| monster.name == "ork"));
+        assert!(content.items.iter().any(|item| item.name == "healing potion"));
+        assert!(!content.vaults.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_duplicate_name() {
+        let error = parse_monsters("monsters.toml", &format!("{}{}", ORK, ORK)).err().unwrap();
+        assert_eq!(error.path, "monsters.toml");
+        assert_eq!(error.message, "monster \"ork\": defined more than once");
+    }
+
+    #[test]
+    fn rejects_spawn_levels_that_do_not_increase() {
+        let text = ORK.replace("[{ level = 1, weight = 80 }]", "[{ level = 3, weight = 80 }, { level = 3, weight = 40 }]");
+        let error = parse_monsters("monsters.toml", &text).err().unwrap();
+        assert_eq!(error.message, "monster \"ork\": `spawn` levels must be increasing");
+    }
+
+    #[test]
+    fn rejects_a_launcher_without_its_ammo() {
+        let text = r#"
+[[item]]
+name = "bow"
+glyph = ")"
+color = [191, 127, 63]
+effect = "equip"
+equipment = { slot = "main_hand" }
+launcher = { ammo = "arrow", range = 8, power = 4 }
+spawn = [{ level = 1, weight = 5 }]
+"#;
+        let error = parse_items("items.toml", text).err().unwrap();
+        assert_eq!(error.message, "item \"bow\": the launcher ammo \"arrow\" is no item with effect \"ammo\"");
+    }
+
+    #[test]
+    fn rejects_a_vault_door_that_does_not_lead_out() {
+        let text = r#"
+[[vault]]
+name = "closet"
+map = '''
+#####
+#.+.#
+#####
+'''
+spawn = [{ level = 1, weight = 10 }]
+"#;
+        let error = parse_vaults("vaults.toml", text, &[], &[]).err().unwrap();
+        assert_eq!(error.message, "vault \"closet\": the door at line 2, column 3 does not lead out");
+    }
+}
@@ -0,0 +1,313 @@
+//! Entities and their components.
+//!
+//! An entity is only an id, everything it is and does comes from the components attached to it. Components live
+//! in one `Storage` per type inside the `World`; adding a kind of component is one line in the `world!` list below.
+//!
+//! Entity ids are generational: an id names one slot and the generation the slot was in when the entity was
+//! spawned. Despawning bumps the generation, so old ids stop matching instead of pointing at whatever reuses the
+//! slot.
+
+use serde::{Deserialize, Serialize};
+use crate::ai::Ai;
+use crate::components::{BlocksMovement, Equipment, Fighter, Level, Locomotion, Name, Position, Potion, Quantity, Renderable, Stairs};
+use crate::effects::{OnHit, StatusEffects};
+use crate::inventory::Item;
+use crate::ranged::{Launcher, RangedAttack};
+use crate::scheduler::Actor;
+use crate::traps::Trap;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+impl Entity {
+    /// The first entity spawned into an empty world.
+    pub const FIRST: Entity = Entity { index: 0, generation: 0 };
+}
+
+/// Components of one type, indexed by the entity's slot.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Storage<C> {
+    slots: Vec<Option<(Entity, C)>>,
+}
+
+impl<C> Default for Storage<C> {
+    fn default() -> Self {
+        Self { slots: vec![] }
+    }
+}
+
+impl<C> Storage<C> {
+    pub fn get(&self, entity: Entity) -> Option<&C> {
+        match self.slots.get(entity.index) {
+            Some(Some((owner, component))) if *owner == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut C> {
+        match self.slots.get_mut(entity.index) {
+            Some(Some((owner, component))) if *owner == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    /// Adds or replaces the entity's component. A stale id whose slot already belongs to a newer entity is ignored.
+    pub fn insert(&mut self, entity: Entity, component: C) {
+        if self.slots.len() <= entity.index {
+            self.slots.resize_with(entity.index + 1, || None);
+        }
+        let slot = &mut self.slots[entity.index];
+        if slot.as_ref().is_none_or(|(owner, _)| owner.generation <= entity.generation) {
+            *slot = Some((entity, component));
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<C> {
+        let slot = self.slots.get_mut(entity.index)?;
+        match slot {
+            Some((owner, _)) if *owner == entity => slot.take().map(|(_, component)| component),
+            _ => None,
+        }
+    }
+
+    /// Every entity with the component, in spawn slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &C)> {
+        self.slots.iter().filter_map(|slot| slot.as_ref().map(|(entity, component)| (*entity, component)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut C)> {
+        self.slots.iter_mut().filter_map(|slot| slot.as_mut().map(|(entity, component)| (*entity, component)))
+    }
+}
+
+/// Lets `World::get` and friends find the storage of a component type.
+pub trait Component: Sized + 'static {
+    fn storage(world: &World) -> &Storage<Self>;
+
+    fn storage_mut(world: &mut World) -> &mut Storage<Self>;
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    /// Slots of despawned entities, reused by `spawn`.
+    free: Vec<usize>,
+}
+
+macro_rules! world {
+    ($($field:ident: $component:ty,)*) => {
+        #[derive(Debug, Default, Serialize, Deserialize)]
+        pub struct World {
+            entities: Entities,
+            $(pub $field: Storage<$component>,)*
+        }
+
+        $(
+            impl Component for $component {
+                fn storage(world: &World) -> &Storage<Self> {
+                    &world.$field
+                }
+
+                fn storage_mut(world: &mut World) -> &mut Storage<Self> {
+                    &mut world.$field
+                }
+            }
+        )*
+
+        impl World {
+            fn remove_components(&mut self, entity: Entity) {
+                $(self.$field.remove(entity);)*
+            }
+
+            fn copy_components(&mut self, from: Entity, to: Entity) {
+                $(
+                    if let Some(component) = self.$field.get(from).cloned() {
+                        self.$field.insert(to, component);
+                    }
+                )*
+            }
+        }
+    };
+}
+
+world! {
+    positions: Position,
+    renderables: Renderable,
+    names: Name,
+    blockers: BlocksMovement,
+    stairs: Stairs,
+    fighters: Fighter,
+    levels: Level,
+    ais: Ai,
+    actors: Actor,
+    items: Item,
+    equipment: Equipment,
+    quantities: Quantity,
+    potions: Potion,
+    locomotions: Locomotion,
+    traps: Trap,
+    status_effects: StatusEffects,
+    on_hits: OnHit,
+    launchers: Launcher,
+    ranged_attacks: RangedAttack,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts a new entity, components are added with `EntityBuilder::with`.
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        let entities = &mut self.entities;
+        let entity = match entities.free.pop() {
+            Some(index) => {
+                entities.alive[index] = true;
+                Entity { index, generation: entities.generations[index] }
+            }
+            None => {
+                entities.generations.push(0);
+                entities.alive.push(true);
+                Entity { index: entities.generations.len() - 1, generation: 0 }
+            }
+        };
+        EntityBuilder { world: self, entity }
+    }
+
+    /// Removes the entity with all its components, every copy of its id becomes stale.
+    pub fn despawn(&mut self, entity: Entity) {
+        if self.contains(entity) {
+            self.remove_components(entity);
+            self.entities.alive[entity.index] = false;
+            self.entities.generations[entity.index] += 1;
+            self.entities.free.push(entity.index);
+        }
+    }
+
+    /// A new entity with copies of every component of `entity`.
+    pub fn duplicate(&mut self, entity: Entity) -> Entity {
+        let copy = self.spawn().build();
+        self.copy_components(entity, copy);
+        copy
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.alive.get(entity.index) == Some(&true) && self.entities.generations[entity.index] == entity.generation
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.generations.iter()
+            .zip(&self.entities.alive)
+            .enumerate()
+            .filter(|(_, (_, &alive))| alive)
+            .map(|(index, (&generation, _))| Entity { index, generation })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.alive.iter().filter(|&&alive| alive).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get<C: Component>(&self, entity: Entity) -> Option<&C> {
+        C::storage(self).get(entity)
+    }
+
+    pub fn get_mut<C: Component>(&mut self, entity: Entity) -> Option<&mut C> {
+        C::storage_mut(self).get_mut(entity)
+    }
+
+    pub fn has<C: Component>(&self, entity: Entity) -> bool {
+        C::storage(self).contains(entity)
+    }
+
+    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) {
+        debug_assert!(self.contains(entity), "component added to a despawned entity");
+        C::storage_mut(self).insert(entity, component);
+    }
+
+    pub fn remove<C: Component>(&mut self, entity: Entity) -> Option<C> {
+        C::storage_mut(self).remove(entity)
+    }
+
+    /// Every entity with a component of type `C`, with the component.
+    pub fn query<C: Component>(&self) -> impl Iterator<Item = (Entity, &C)> {
+        C::storage(self).iter()
+    }
+}
+
+pub struct EntityBuilder<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl EntityBuilder<'_> {
+    pub fn with<C: Component>(self, component: C) -> Self {
+        self.world.insert(self.entity, component);
+        self
+    }
+
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_despawned_id_stays_stale_when_its_slot_is_reused() {
+        let mut world = World::new();
+        let old = world.spawn().with(Name::new("ork")).build();
+        world.despawn(old);
+        let new = world.spawn().with(Name::new("troll")).build();
+
+        assert_eq!(new.index, old.index);
+        assert!(!world.contains(old));
+        assert!(world.get::<Name>(old).is_none());
+        assert!(!world.names.contains(old));
+        assert_eq!(world.get::<Name>(new), Some(&Name::new("troll")));
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![new]);
+    }
+
+    #[test]
+    fn despawning_a_stale_id_leaves_the_new_entity_alone() {
+        let mut world = World::new();
+        let old = world.spawn().build();
+        world.despawn(old);
+        let new = world.spawn().with(Name::new("troll")).build();
+        world.despawn(old);
+
+        assert!(world.contains(new));
+        assert!(world.names.contains(new));
+    }
+
+    #[test]
+    fn storage_ignores_stale_ids() {
+        let mut world = World::new();
+        let old = world.spawn().build();
+        world.despawn(old);
+        let new = world.spawn().build();
+
+        let mut storage = Storage::default();
+        storage.insert(new, 1);
+        assert_eq!(storage.remove(old), None);
+        assert_eq!(storage.get(new), Some(&1));
+
+        storage.insert(old, 2);
+        assert_eq!(storage.get(old), None);
+        assert_eq!(storage.get(new), Some(&1));
+        assert_eq!(storage.remove(new), Some(1));
+    }
+}
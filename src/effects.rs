@@ -0,0 +1,163 @@
+//! Timed status effects like poison or haste, worn off one turn at a time.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::{DARK_GREEN, GREY, LIGHT_BLUE, LIGHT_GREEN, LIGHT_VIOLET, LIGHT_YELLOW, WHITE};
+use tcod::{Color, Map};
+use crate::{Game, PLAYER_ID};
+use crate::combat::{heal, take_damage};
+use crate::ecs::{Entity, World};
+
+const POISON_DAMAGE: i32 = 1;
+const REGENERATION_AMOUNT: i32 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectKind {
+    /// Loses hit points every turn.
+    Poisoned,
+    /// Stumbles around in random directions.
+    Confused,
+    /// Cannot act at all.
+    Paralysed,
+    /// Acts twice as often.
+    Hasted,
+    /// Gains hit points every turn.
+    Regenerating,
+    /// Sees only the tiles next to it and hits for half damage.
+    Blinded,
+}
+
+impl EffectKind {
+    fn name(self) -> &'static str {
+        match self {
+            EffectKind::Poisoned => "poisoned",
+            EffectKind::Confused => "confused",
+            EffectKind::Paralysed => "paralysed",
+            EffectKind::Hasted => "hasted",
+            EffectKind::Regenerating => "regenerating",
+            EffectKind::Blinded => "blinded",
+        }
+    }
+
+    /// Short tag shown in the panel.
+    pub fn tag(self) -> &'static str {
+        match self {
+            EffectKind::Poisoned => "PSN",
+            EffectKind::Confused => "CNF",
+            EffectKind::Paralysed => "PAR",
+            EffectKind::Hasted => "HST",
+            EffectKind::Regenerating => "RGN",
+            EffectKind::Blinded => "BLD",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            EffectKind::Poisoned => DARK_GREEN,
+            EffectKind::Confused => LIGHT_VIOLET,
+            EffectKind::Paralysed => GREY,
+            EffectKind::Hasted => LIGHT_YELLOW,
+            EffectKind::Regenerating => LIGHT_GREEN,
+            EffectKind::Blinded => LIGHT_BLUE,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: EffectKind,
+    /// Turns left before it wears off.
+    pub turns: i32,
+}
+
+/// The effects a creature is under, at most one of each kind.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+/// A monster's attacks that hit may put the target under an effect.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OnHit {
+    pub effect: EffectKind,
+    pub turns: i32,
+    /// Percentage chance per hit.
+    pub chance: u32,
+}
+
+pub fn has_effect(entity: Entity, kind: EffectKind, world: &World) -> bool {
+    world.status_effects.get(entity).is_some_and(|effects| effects.0.iter().any(|effect| effect.kind == kind))
+}
+
+/// Puts the entity under the effect, an effect it is already under lasts the longer of the two durations.
+pub fn apply_effect(entity: Entity, kind: EffectKind, turns: i32, world: &mut World) {
+    if world.status_effects.get(entity).is_none() {
+        world.insert(entity, StatusEffects::default());
+    }
+    let effects = &mut world.status_effects.get_mut(entity).unwrap().0;
+    match effects.iter_mut().find(|effect| effect.kind == kind) {
+        Some(effect) => effect.turns = effect.turns.max(turns),
+        None => effects.push(StatusEffect { kind, turns }),
+    }
+}
+
+/// Rolls the attacker's on-hit effect against the target it just hurt.
+pub fn apply_on_hit(attacker: Entity, target: Entity, game: &mut Game, world: &mut World) {
+    let on_hit = match world.get::<OnHit>(attacker) {
+        Some(&on_hit) => on_hit,
+        None => return,
+    };
+    if !world.is_alive(target) || game.rng.gen_range(0..100) >= on_hit.chance {
+        return;
+    }
+    apply_effect(target, on_hit.effect, on_hit.turns, world);
+    let message = if target == PLAYER_ID {
+        format!("You are {}!", on_hit.effect.name())
+    } else {
+        format!("The {} is {}!", world.name(target), on_hit.effect.name())
+    };
+    game.messages.add(message, on_hit.effect.color());
+}
+
+/// Runs one turn of every effect and drops the ones that wore off. Only the player and the monsters in view
+/// are reported on.
+pub fn tick_effects(fov_map: &Map, game: &mut Game, world: &mut World) {
+    let affected: Vec<Entity> = world.status_effects.iter().map(|(entity, _)| entity).collect();
+    for entity in affected {
+        if has_effect(entity, EffectKind::Poisoned, world) {
+            if entity == PLAYER_ID {
+                game.messages.add(format!("The poison hurts you for {} hit points.", POISON_DAMAGE), DARK_GREEN);
+            }
+            take_damage(entity, POISON_DAMAGE, game, world);
+        }
+        if has_effect(entity, EffectKind::Regenerating, world) && world.is_alive(entity) {
+            heal(entity, REGENERATION_AMOUNT, game, world);
+        }
+
+        // the poison may have killed it, and dead monsters lose their effects with their fighter
+        if !world.is_alive(entity) {
+            world.remove::<StatusEffects>(entity);
+            continue;
+        }
+        let in_view = entity == PLAYER_ID || world.position(entity).is_some_and(|(x, y)| fov_map.is_in_fov(x, y));
+        let effects = &mut world.status_effects.get_mut(entity).unwrap().0;
+        for effect in effects.iter_mut() {
+            effect.turns -= 1;
+        }
+        let worn_off: Vec<EffectKind> = effects.iter().filter(|effect| effect.turns <= 0).map(|effect| effect.kind).collect();
+        effects.retain(|effect| effect.turns > 0);
+        if effects.is_empty() {
+            world.remove::<StatusEffects>(entity);
+        }
+        if in_view {
+            for kind in worn_off {
+                let message = if entity == PLAYER_ID {
+                    format!("You are no longer {}.", kind.name())
+                } else {
+                    format!("The {} is no longer {}.", world.name(entity), kind.name())
+                };
+                game.messages.add(message, WHITE);
+            }
+        }
+    }
+}
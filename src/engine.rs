@@ -0,0 +1,510 @@
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use tcod::colors::*;
+use tcod::map::{FovAlgorithm, Map as FovMap};
+use tcod::TextAlignment;
+use crate::{Game, gamemap, PLAYER_ID, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::backend::{Event, Input, Key, Mouse, Renderer};
+use crate::content::Content;
+use crate::gamemap::{draw_map, search, GameMap, MapGenerator, TileKind, MAP_HEIGHT, MAP_WIDTH};
+use crate::gui::{draw_gui, Messages, DEFAULT_MESSAGE_LIMIT};
+use crate::inventory::{drop_item, pick_item_up, throw_item, use_item};
+use crate::keymap::{Command, Keymap};
+use crate::menu::{inventory_menu, menu, msgbox};
+use crate::message_log::show_message_log;
+use crate::movement::player_move_or_attack;
+use crate::combat::{heal, level_up_xp, max_hp, take_damage};
+use crate::components::{BlocksMovement, DeathCallback, Fighter, Level, Name, Position, Renderable};
+use crate::ecs::World;
+use crate::effects::{has_effect, EffectKind};
+use crate::ranged::{equipped_launcher, find_ammo, fire};
+use crate::save::{load_game, save_game, SAVE_FILE};
+use crate::scheduler::{Action, Actor, NORMAL_SPEED, run_until_player_ready, spend_energy};
+use crate::targeting::target_tile;
+use crate::terrain::FALL_DAMAGE;
+use crate::traps::{detect_traps, disarm, is_hidden, known_trap_at};
+use PlayerAction::{DidntTakeTurn, Exit, TookTurn};
+
+const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
+const FOV_LIGHT_WALLS: bool = true;
+const TORCH_RADIUS: i32 = 10;
+/// A blinded player only makes out the tiles next to them.
+const BLIND_RADIUS: i32 = 1;
+
+const LEVEL_SCREEN_WIDTH: i32 = 40;
+const HELP_SCREEN_WIDTH: i32 = 60;
+
+pub struct Ui<'a> {
+    pub renderer: &'a mut dyn Renderer,
+    pub input: &'a mut dyn Input,
+    pub fov: FovMap,
+    pub key: Key,
+    pub mouse: Mouse,
+    pub keymap: Keymap,
+    pub content: Content,
+    /// Where the game is saved on quitting and loaded from.
+    pub save_file: String,
+    /// Messages kept in the log, older ones are dropped.
+    pub message_limit: usize,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(renderer: &'a mut dyn Renderer, input: &'a mut dyn Input, keymap: Keymap, content: Content) -> Self {
+        Self {
+            renderer,
+            input,
+            fov: FovMap::new(MAP_WIDTH, MAP_HEIGHT),
+            key: Default::default(),
+            mouse: Default::default(),
+            keymap,
+            content,
+            save_file: SAVE_FILE.into(),
+            message_limit: DEFAULT_MESSAGE_LIMIT,
+        }
+    }
+
+    /// The window was closed or, headless, the scripted input ran out.
+    pub fn closed(&self) -> bool {
+        self.renderer.window_closed() || self.input.finished()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PlayerAction {
+    TookTurn(Action),
+    DidntTakeTurn,
+    Exit,
+}
+
+pub fn main_menu(ui: &mut Ui, seed: Option<u64>, map_generator: MapGenerator) {
+    while !ui.closed() {
+        ui.renderer.clear();
+        ui.renderer.print(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2 - 4, "ROGUELIKE", LIGHT_YELLOW, TextAlignment::Center);
+
+        let choices = &["Play a new game", "Continue last game", "Quit"];
+        let choice = menu("", choices, 24, ui.renderer, ui.input);
+
+        match choice {
+            Some(0) => {
+                let (mut game, mut world) = new_game(ui, seed.unwrap_or_else(rand::random), map_generator);
+                play_game(ui, &mut game, &mut world);
+            }
+            Some(1) => {
+                match load_game(&ui.save_file) {
+                    Ok((mut game, mut world)) => {
+                        game.messages.set_limit(ui.message_limit);
+                        initialise_fov(ui, &game.map);
+                        play_game(ui, &mut game, &mut world);
+                    }
+                    Err(e) => {
+                        msgbox(&format!("\nCannot load saved game: {}\n", e), 24, ui.renderer, ui.input);
+                        continue;
+                    }
+                }
+            }
+            Some(2) => break,
+            _ => {}
+        }
+    }
+}
+
+pub fn new_game(ui: &mut Ui, seed: u64, map_generator: MapGenerator) -> (Game, World) {
+    let mut world = World::new();
+    let player = world.spawn()
+        .with(Position::new(25, 23))
+        .with(Renderable::new('@', WHITE))
+        .with(Name::new("Franta"))
+        .with(BlocksMovement)
+        .with(Fighter { max_hp: 30, hp: 30, defense: 2, power: 5, xp: 0, on_death: DeathCallback::Player })
+        .with(Level(1))
+        .with(Actor::ready(NORMAL_SPEED))
+        .build();
+    assert_eq!(player, PLAYER_ID);
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let mut game = Game {
+        map: gamemap::make_map(&mut world, 1, map_generator, &ui.content, &mut rng),
+        messages: Messages::with_limit(ui.message_limit),
+        inventory: vec![],
+        dungeon_level: 1,
+        map_generator,
+        seed,
+        rng,
+    };
+    game.messages.add("Welcome stranger!", RED);
+
+    initialise_fov(ui, &game.map);
+
+    (game, world)
+}
+
+fn level_up(ui: &mut Ui, game: &mut Game, world: &mut World) {
+    let level = world.get::<Level>(PLAYER_ID).map_or(1, |l| l.0);
+    let level_up_xp = level_up_xp(level);
+    if world.fighters.get(PLAYER_ID).map_or(0, |f| f.xp) >= level_up_xp {
+        world.insert(PLAYER_ID, Level(level + 1));
+        game.messages.add(format!("Your battle skills grow stronger! You reached level {}!", level + 1), YELLOW);
+
+        let fighter = world.fighters.get_mut(PLAYER_ID).unwrap();
+        let mut choice = None;
+        while choice.is_none() && !ui.closed() {
+            choice = menu(
+                "Level up! Choose a stat to raise:\n",
+                &[
+                    format!("Constitution (+20 HP, from {})", fighter.max_hp),
+                    format!("Strength (+1 attack, from {})", fighter.power),
+                    format!("Agility (+1 defense, from {})", fighter.defense),
+                ],
+                LEVEL_SCREEN_WIDTH,
+                ui.renderer,
+                ui.input,
+            );
+        }
+        let choice = match choice {
+            Some(choice) => choice,
+            // the game is over before the player chose
+            None => return,
+        };
+        fighter.xp -= level_up_xp;
+        match choice {
+            0 => {
+                fighter.max_hp += 20;
+                fighter.hp += 20;
+            }
+            1 => fighter.power += 1,
+            2 => fighter.defense += 1,
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn next_level(ui: &mut Ui, game: &mut Game, world: &mut World) {
+    game.messages.add("You take a moment to rest, and recover your strength.", VIOLET);
+    let heal_hp = max_hp(PLAYER_ID, game, world) / 2;
+    heal(PLAYER_ID, heal_hp, game, world);
+
+    game.messages.add("After a rare moment of peace, you descend deeper into the heart of the dungeon...", RED);
+    descend(ui, game, world);
+}
+
+/// Drops the player down a chasm to the next level, with no rest on the way.
+fn fall_into_chasm(ui: &mut Ui, game: &mut Game, world: &mut World) {
+    game.messages.add("You fall into the chasm!", RED);
+    descend(ui, game, world);
+    game.messages.add(format!("You land hard and take {} damage.", FALL_DAMAGE), RED);
+    take_damage(PLAYER_ID, FALL_DAMAGE, game, world);
+}
+
+fn descend(ui: &mut Ui, game: &mut Game, world: &mut World) {
+    game.dungeon_level += 1;
+    game.map = gamemap::make_map(world, game.dungeon_level, game.map_generator, &ui.content, &mut game.rng);
+    initialise_fov(ui, &game.map);
+}
+
+pub fn initialise_fov(ui: &mut Ui, map: &GameMap) {
+    update_fov_map(ui, map);
+
+    // the screen still holds the previous game's map
+    ui.renderer.clear();
+}
+
+/// Copies which tiles block sight and movement into the FOV map.
+fn update_fov_map(ui: &mut Ui, map: &GameMap) {
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let tile = &map[x as usize][y as usize];
+            ui.fov.set(x, y, !tile.block_sight(), !tile.blocked());
+        }
+    }
+}
+
+fn sight_radius(world: &World) -> i32 {
+    if has_effect(PLAYER_ID, EffectKind::Blinded, world) { BLIND_RADIUS } else { TORCH_RADIUS }
+}
+
+fn compute_fov(ui: &mut Ui, world: &World) {
+    let (px, py) = world.player_position();
+    ui.fov.compute_fov(px, py, sight_radius(world), FOV_LIGHT_WALLS, FOV_ALGO);
+}
+
+/// Runs the game until the player quits (the game is saved) or the window is closed.
+pub fn play_game(ui: &mut Ui, game: &mut Game, world: &mut World) {
+    let mut previous_player_position = (-1, -1);
+    let mut previous_sight_radius = sight_radius(world);
+    while !ui.closed() {
+        match ui.input.check_for_event() {
+            Some(Event::Mouse(m)) => ui.mouse = m,
+            Some(Event::Key(k)) => ui.key = k,
+            _ => ui.key = Default::default(),
+        }
+
+        let fov_recompute = previous_player_position != world.player_position() || previous_sight_radius != sight_radius(world);
+        render(ui, game, world, fov_recompute);
+        ui.renderer.flush();
+
+        previous_player_position = world.player_position();
+        previous_sight_radius = sight_radius(world);
+        let player_action = if has_effect(PLAYER_ID, EffectKind::Paralysed, world) && world.is_alive(PLAYER_ID) {
+            // the turn passes without the player
+            game.messages.add("You cannot move!", LIGHT_GREY);
+            TookTurn(Action::Wait)
+        } else {
+            handle_keys(ui, world, game)
+        };
+
+        level_up(ui, game, world);
+
+        if let TookTurn(action) = player_action {
+            if matches!(action, Action::OpenDoor | Action::CloseDoor) {
+                // the monsters act before the next render and should already see the door changed
+                update_fov_map(ui, &game.map);
+                compute_fov(ui, world);
+            }
+            spend_energy(PLAYER_ID, action, world);
+            let (px, py) = world.player_position();
+            if game.map[px as usize][py as usize].kind == TileKind::Chasm {
+                fall_into_chasm(ui, game, world);
+                compute_fov(ui, world);
+            }
+            run_until_player_ready(&ui.fov, game, world);
+            // monsters may have opened doors
+            update_fov_map(ui, &game.map);
+            compute_fov(ui, world);
+        }
+
+        if player_action == Exit {
+            if let Err(e) = save_game(&ui.save_file, game, world) {
+                msgbox(&format!("\nCannot save the game: {}\n", e), 24, ui.renderer, ui.input);
+            }
+            break;
+        }
+    }
+}
+
+/// Draws the map, every visible entity with a position and a look, and the panel.
+pub(crate) fn render(ui: &mut Ui, game: &mut Game, world: &World, fov_recompute: bool) {
+    ui.renderer.clear();
+
+    if fov_recompute {
+        compute_fov(ui, world);
+    }
+
+    draw_map(game, ui.renderer, &ui.fov);
+
+    let mut to_draw: Vec<_> = world.renderables.iter()
+        .filter_map(|(entity, renderable)| world.positions.get(entity).map(|position| (entity, position, renderable)))
+        .filter(|&(entity, position, renderable)| {
+            let (x, y) = (position.x, position.y);
+            if is_hidden(entity, world) {
+                return false;
+            }
+            ui.fov.is_in_fov(x, y) || (renderable.always_visible && game.map[x as usize][y as usize].explored)
+        })
+        .collect();
+    // blocking entities, the creatures, are drawn over items and corpses
+    to_draw.sort_by_key(|&(entity, _, _)| world.blockers.contains(entity));
+    for (_, position, renderable) in to_draw {
+        ui.renderer.put_char(position.x, position.y, renderable.glyph, renderable.color);
+    }
+
+    draw_gui(ui.renderer, world, game, &ui.mouse, &ui.fov);
+}
+
+fn look(ui: &mut Ui, game: &mut Game, world: &World) {
+    game.messages.add("Look at what? Move the cursor with the mouse or the movement keys, Enter to select.", LIGHT_CYAN);
+    if let Some((x, y)) = target_tile(ui, game, world, None, 0) {
+        let names = world.entities_at(x, y)
+            .filter(|&entity| !is_hidden(entity, world))
+            .map(|entity| world.name(entity).to_string())
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            game.messages.add("There is nothing interesting there.", WHITE);
+        } else {
+            game.messages.add(format!("You see: {}.", names.join(", ")), WHITE);
+        }
+    }
+}
+
+/// Closes an open door next to the player, asking which one when there are several. Returns whether a door was closed.
+fn close_door(ui: &mut Ui, game: &mut Game, world: &World) -> bool {
+    let (px, py) = world.player_position();
+    let doors: Vec<(i32, i32)> = ((px - 1)..=(px + 1))
+        .flat_map(|x| ((py - 1)..=(py + 1)).map(move |y| (x, y)))
+        .filter(|&(x, y)| game.map[x as usize][y as usize].kind == TileKind::OpenDoor)
+        .collect();
+    if doors.is_empty() {
+        game.messages.add("There is no open door next to you.", WHITE);
+        return false;
+    }
+    let (x, y) = match choose_adjacent(ui, game, world, &doors, "Close the door in which direction?") {
+        Some(door) => door,
+        None => return false,
+    };
+
+    // a trap does not keep the door open
+    if world.entities_at(x, y).any(|e| !world.traps.contains(e)) {
+        game.messages.add("Something is in the way.", WHITE);
+        return false;
+    }
+    game.map[x as usize][y as usize].kind = TileKind::ClosedDoor;
+    true
+}
+
+/// Disarms a trap found next to or under the player, asking which one when there are several. Returns whether
+/// the player tried.
+fn disarm_trap(ui: &mut Ui, game: &mut Game, world: &mut World) -> bool {
+    let (px, py) = world.player_position();
+    let traps: Vec<(i32, i32)> = ((px - 1)..=(px + 1))
+        .flat_map(|x| ((py - 1)..=(py + 1)).map(move |y| (x, y)))
+        .filter(|&(x, y)| known_trap_at(x, y, world).is_some())
+        .collect();
+    if traps.is_empty() {
+        game.messages.add("There is no trap you know of next to you.", WHITE);
+        return false;
+    }
+    let trap = choose_adjacent(ui, game, world, &traps, "Disarm the trap in which direction?")
+        .and_then(|(x, y)| known_trap_at(x, y, world));
+    match trap {
+        Some(trap) => {
+            disarm(trap, game, world);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Picks one of the tiles around the player, asking for a direction when there is more than one.
+fn choose_adjacent(ui: &mut Ui, game: &mut Game, world: &World, tiles: &[(i32, i32)], question: &str) -> Option<(i32, i32)> {
+    if let [tile] = tiles {
+        return Some(*tile);
+    }
+    game.messages.add(question, LIGHT_CYAN);
+    render(ui, game, world, false);
+    ui.renderer.flush();
+    let (px, py) = world.player_position();
+    match ui.keymap.command(ui.input.wait_for_keypress()) {
+        Some(Command::Move(dx, dy)) if tiles.contains(&(px + dx, py + dy)) => Some((px + dx, py + dy)),
+        // standing on it
+        Some(Command::Wait) if tiles.contains(&(px, py)) => Some((px, py)),
+        _ => None,
+    }
+}
+
+fn handle_keys(ui: &mut Ui, world: &mut World, game: &mut Game) -> PlayerAction {
+    use Command::*;
+    match (ui.keymap.command(ui.key), world.is_alive(PLAYER_ID)) {
+        (Some(Move(dx, dy)), true) => {
+            TookTurn(player_move_or_attack(dx, dy, game, world))
+        }
+        (Some(Wait), true) => TookTurn(Action::Wait),
+
+        (Some(PickUp), true) => {
+            let (px, py) = world.player_position();
+            let item = world.entities_at(px, py).find(|&e| world.items.contains(e));
+            if let Some(item) = item {
+                pick_item_up(item, game, world);
+            }
+            DidntTakeTurn
+        }
+
+        (Some(Descend), true) => {
+            let (px, py) = world.player_position();
+            let player_on_stairs = world.entities_at(px, py).any(|e| world.stairs.contains(e));
+            if player_on_stairs {
+                next_level(ui, game, world);
+            }
+            DidntTakeTurn
+        }
+
+        (Some(Look), true) => {
+            look(ui, game, world);
+            DidntTakeTurn
+        }
+
+        (Some(CloseDoor), true) => {
+            if close_door(ui, game, world) {
+                TookTurn(Action::CloseDoor)
+            } else {
+                DidntTakeTurn
+            }
+        }
+
+        (Some(Search), true) => {
+            let (px, py) = world.player_position();
+            let doors = search(px, py, &mut game.map, &mut game.rng);
+            let traps = detect_traps(px, py, game, world);
+            if doors > 0 {
+                game.messages.add("You find a secret door!", LIGHT_CYAN);
+            }
+            if traps > 0 {
+                game.messages.add("You find a trap!", LIGHT_CYAN);
+            }
+            if doors == 0 && traps == 0 {
+                game.messages.add("You search around but find nothing.", WHITE);
+            }
+            TookTurn(Action::Search)
+        }
+
+        (Some(Disarm), true) => {
+            if disarm_trap(ui, game, world) {
+                TookTurn(Action::Disarm)
+            } else {
+                DidntTakeTurn
+            }
+        }
+
+        (Some(Inventory), true) => {
+            let inventory_index = inventory_menu(&game.inventory, world, "Press the key to an item to use it, or any other to cancel.\n", ui.renderer, ui.input);
+            match inventory_index {
+                Some(inventory_index) if use_item(inventory_index, ui, game, world) => TookTurn(Action::UseItem),
+                _ => DidntTakeTurn,
+            }
+        }
+
+        (Some(Drop), true) => {
+            let inventory_index = inventory_menu(&game.inventory, world, "Press the key next to an item to drop it, or any other to cancel.\n", ui.renderer, ui.input);
+            if let Some(inventory_index) = inventory_index {
+                drop_item(inventory_index, game, world);
+            }
+            DidntTakeTurn
+        }
+
+        (Some(Throw), true) => {
+            let inventory_index = inventory_menu(&game.inventory, world, "Press the key next to an item to throw it, or any other to cancel.\n", ui.renderer, ui.input);
+            match inventory_index {
+                Some(inventory_index) if throw_item(inventory_index, ui, game, world) => TookTurn(Action::Throw),
+                _ => DidntTakeTurn,
+            }
+        }
+
+        (Some(Fire), true) => {
+            match find_ammo(game, world) {
+                Some(ammo_index) if fire(ammo_index, ui, game, world) => TookTurn(Action::Fire),
+                Some(_) => DidntTakeTurn,
+                None => {
+                    match equipped_launcher(game, world) {
+                        Some(launcher) => game.messages.add(format!("You have no {} left.", launcher.ammo), RED),
+                        None => game.messages.add("You hold nothing to shoot with.", RED),
+                    }
+                    DidntTakeTurn
+                }
+            }
+        }
+
+        (Some(MessageLog), _) => {
+            show_message_log(&game.messages, ui.renderer, ui.input);
+            DidntTakeTurn
+        }
+
+        (Some(Help), _) => {
+            msgbox(&format!("Commands\n\n{}\n", ui.keymap.help_text()), HELP_SCREEN_WIDTH, ui.renderer, ui.input);
+            DidntTakeTurn
+        }
+
+        (Some(ToggleFullscreen), _) => {
+            ui.renderer.toggle_fullscreen();
+            DidntTakeTurn
+        }
+        (Some(Quit), _) => Exit,
+        _ => DidntTakeTurn
+    }
+}
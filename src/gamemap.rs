@@ -1,12 +1,20 @@
 use std::cmp;
+use std::collections::{HashSet, VecDeque};
+use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
-use tcod::{BackgroundFlag, Color, colors, Console, Map};
-use tcod::colors::VIOLET;
-use DeathCallback::Monster;
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use tcod::{Color, Map};
+use tcod::colors::{BLACK, DARKER_GREY, DARK_RED, GREY, LIGHT_BLUE, WHITE, YELLOW};
 use crate::{Game, PLAYER_ID};
-use crate::ai::Ai;
-use crate::inventory::Item;
-use crate::object::{DeathCallback, Fighter, Object};
+use crate::backend::Renderer;
+use crate::components::{Name, Position, Renderable, Stairs};
+use crate::content::Content;
+use crate::ecs::{Entity, World};
+use crate::terrain::can_enter;
+use crate::traps::{spawn_trap, TrapKind};
+use crate::vault::place_vault;
 
 pub const MAP_WIDTH: i32 = 80;
 pub const MAP_HEIGHT: i32 = 43;
@@ -17,58 +25,436 @@ const COLOR_LIGHT_WALL: Color = Color { r: 130, g: 110, b: 50 };
 const COLOR_DARK_GROUND: Color = Color { r: 50, g: 50, b: 150 };
 const COLOR_LIGHT_GROUND: Color = Color { r: 200, g: 180, b: 50 };
 
+const COLOR_DARK_WATER: Color = Color { r: 0, g: 0, b: 60 };
+const COLOR_LIGHT_WATER: Color = Color { r: 0, g: 60, b: 180 };
+const COLOR_DARK_LAVA: Color = Color { r: 70, g: 20, b: 0 };
+
+const COLOR_DARK_DOOR: Color = Color { r: 70, g: 45, b: 20 };
+const COLOR_LIGHT_DOOR: Color = Color { r: 140, g: 90, b: 40 };
+
 
 const ROOM_MAX_SIZE: i32 = 10;
 const ROOM_MIN_SIZE: i32 = 6;
 const MAX_ROOMS: i32 = 30;
-const MAX_ROOM_MONSTERS: i32 = 3;
-const MAX_ROOM_ITEMS: i32 = 2;
+
+/// How many times the BSP generator halves the map, giving up to 2^depth rooms.
+const BSP_DEPTH: u32 = 4;
+/// Smallest area the BSP generator splits off, a room and a tile of rock on each side.
+const BSP_MIN_SIZE: i32 = ROOM_MIN_SIZE + 2;
+
+/// Percentage of rock in a cave before smoothing.
+const CAVE_ROCK_CHANCE: u32 = 45;
+const CAVE_SMOOTHING_STEPS: u32 = 5;
+/// Caves with less connected floor than this are dug again.
+const CAVE_MIN_FLOOR: usize = 800;
+/// Caves are cut into sectors of this size to spread monsters and items like rooms do.
+const CAVE_SECTOR_WIDTH: i32 = 16;
+const CAVE_SECTOR_HEIGHT: i32 = 11;
+/// Sectors with less floor than this get nothing placed in them.
+const CAVE_SECTOR_MIN_FLOOR: usize = 20;
+
+/// Percentage of the gaps tunnels make in room walls that get a door.
+const DOOR_CHANCE: u32 = 50;
+/// Percentage of those doors that are secret.
+const SECRET_DOOR_CHANCE: u32 = 10;
+/// Percentage chance that searching finds a secret door next to the player.
+const SEARCH_CHANCE: u32 = 35;
+
+/// Percentage of rooms and cave sectors that get a patch of water, lava, chasm or rubble.
+const TERRAIN_CHANCE: u32 = 25;
+/// Most tiles a patch of terrain spreads over.
+const TERRAIN_PATCH_SIZE: usize = 10;
+
+/// Percentage of rooms and cave sectors that hide a trap.
+const TRAP_CHANCE: u32 = 20;
 
 pub type GameMap = Vec<Vec<Tile>>;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Tile {
-    pub blocked: bool,
-    pub block_sight: bool,
+    pub kind: TileKind,
     pub explored: bool,
 }
 
-pub fn make_map(objects: &mut Vec<Object>) -> GameMap {
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileKind {
+    Floor,
+    Wall,
+    /// Opened by walking into it.
+    ClosedDoor,
+    OpenDoor,
+    /// Looks and acts like a wall until found by searching, then it is a closed door.
+    SecretDoor,
+    /// Only swimmers and fliers cross it, the player swims at the risk of losing items.
+    DeepWater,
+    /// Burns everything that does not fly over it.
+    Lava,
+    /// Whoever steps in without wings falls to the next level.
+    Chasm,
+    /// Slow to climb over.
+    Rubble,
+}
+
+/// How a tile kind is drawn in sight, and out of sight once explored.
+struct TileLook {
+    glyph: char,
+    color: Color,
+    dark_color: Color,
+    background: Color,
+    dark_background: Color,
+}
+
+/// Algorithm laying out the rooms of a level.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapGenerator {
+    /// Rooms dropped at random where they fit, each tunnelled to the previous one.
+    Rooms,
+    /// Binary space partition: the map is split in halves, a room put in each part and the halves joined.
+    Bsp,
+    /// Open caves grown with a cellular automaton.
+    Caves,
+    /// Rooms, with caves becoming more common deeper down.
+    Mixed,
+}
+
+/// A dug level before anything is placed on it.
+struct Layout {
+    map: GameMap,
+    /// Floor tiles split into the parts monsters and items are spread over, like the rooms.
+    areas: Vec<Vec<(i32, i32)>>,
+    start: (i32, i32),
+    stairs: (i32, i32),
+}
+
+/// Value that changes with the dungeon depth; the last transition at or below the level applies.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    pub level: u32,
+    /// Only used for spawn weights in the content files, where it is called `weight`.
+    #[serde(rename = "weight")]
+    pub value: u32,
+}
+
+pub fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+    table.iter()
+        .rev()
+        .find(|transition| level >= transition.level)
+        .map_or(0, |transition| transition.value)
+}
+
+pub fn make_map(world: &mut World, level: u32, generator: MapGenerator, content: &Content, rng: &mut Pcg64) -> GameMap {
+    // everything on the floor but the player belongs to the previous level, carried items have no position
+    let previous_level: Vec<_> = world.positions.iter()
+        .map(|(entity, _)| entity)
+        .filter(|&entity| entity != PLAYER_ID)
+        .collect();
+    for entity in previous_level {
+        world.despawn(entity);
+    }
+
+    let generator = match generator {
+        MapGenerator::Mixed => {
+            let cave_chance = from_dungeon_level(&[
+                Transition { level: 3, value: 25 },
+                Transition { level: 5, value: 50 },
+            ], level);
+            if rng.gen_range(0..100) < cave_chance { MapGenerator::Caves } else { MapGenerator::Rooms }
+        }
+        generator => generator,
+    };
+    let mut layout = match generator {
+        MapGenerator::Rooms => rooms_layout(random_rooms(rng), rng),
+        MapGenerator::Bsp => rooms_layout(bsp_rooms(rng), rng),
+        MapGenerator::Caves => caves(rng),
+        MapGenerator::Mixed => unreachable!(),
+    };
+
+    world.set_position(PLAYER_ID, layout.start.0, layout.start.1);
+    place_vault(&mut layout.map, world, level, content, rng);
+    add_terrain(&mut layout, level, rng);
+    for area in &layout.areas {
+        place_objects(area, &layout.map, world, level, content, rng);
+        place_trap(area, &layout, world, level, rng);
+    }
+
+    world.spawn()
+        .with(Position::new(layout.stairs.0, layout.stairs.1))
+        .with(Renderable { always_visible: true, ..Renderable::new('<', WHITE) })
+        .with(Name::new("stairs"))
+        .with(Stairs)
+        .build();
+
+    layout.map
+}
+
+/// The player starts in the first room and the stairs are in the last one.
+fn rooms_layout((mut map, rooms): (GameMap, Vec<RectRoom>), rng: &mut Pcg64) -> Layout {
+    add_doors(&mut map, &rooms, rng);
+    Layout {
+        map,
+        areas: rooms.iter().map(RectRoom::floor).collect(),
+        start: rooms[0].center(),
+        stairs: rooms[rooms.len() - 1].center(),
+    }
+}
+
+fn random_rooms(rng: &mut Pcg64) -> (GameMap, Vec<RectRoom>) {
     let mut game_map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
 
-    let mut rooms = vec![];
+    let mut rooms: Vec<RectRoom> = vec![];
     for _ in 0..MAX_ROOMS {
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE..(ROOM_MAX_SIZE + 1));
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE..(ROOM_MAX_SIZE + 1));
+        let w = rng.gen_range(ROOM_MIN_SIZE..(ROOM_MAX_SIZE + 1));
+        let h = rng.gen_range(ROOM_MIN_SIZE..(ROOM_MAX_SIZE + 1));
 
-        let x = rand::thread_rng().gen_range(0..(MAP_WIDTH - w));
-        let y = rand::thread_rng().gen_range(0..(MAP_HEIGHT - h));
+        let x = rng.gen_range(0..(MAP_WIDTH - w));
+        let y = rng.gen_range(0..(MAP_HEIGHT - h));
 
         let new_room = RectRoom::new(x, y, w, h);
         let failed = rooms.iter()
             .any(|other_room| new_room.intersects_with(other_room));
         if !failed {
             create_room(new_room, &mut game_map);
-            place_objects(new_room, &game_map, objects);
-
-            let (new_x, new_y) = new_room.center();
-            if rooms.is_empty() {
-                objects[PLAYER_ID].move_to(new_x, new_y)
-            } else {
-                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
-                if rand::random() {
-                    create_h_tunnel(prev_x, new_x, prev_y, &mut game_map);
-                    create_v_tunnel(prev_y, new_y, new_x, &mut game_map);
-                } else {
-                    create_v_tunnel(prev_y, new_y, prev_x, &mut game_map);
-                    create_h_tunnel(prev_x, new_x, new_y, &mut game_map);
-                }
+            if let Some(&prev_room) = rooms.last() {
+                connect_rooms(prev_room, new_room, &mut game_map, rng);
             }
             rooms.push(new_room)
         }
     }
 
-    game_map
+    (game_map, rooms)
+}
+
+fn bsp_rooms(rng: &mut Pcg64) -> (GameMap, Vec<RectRoom>) {
+    let mut game_map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut rooms = vec![];
+    let whole_map = RectRoom::new(0, 0, MAP_WIDTH - 1, MAP_HEIGHT - 1);
+    bsp_split(whole_map, BSP_DEPTH, &mut game_map, &mut rooms, rng);
+    (game_map, rooms)
+}
+
+/// Halves the area until it is too small or deep enough, digs a room in every part and joins the halves.
+/// Returns a room of the area for the caller to join to the other half.
+fn bsp_split(area: RectRoom, depth: u32, map: &mut GameMap, rooms: &mut Vec<RectRoom>, rng: &mut Pcg64) -> RectRoom {
+    let (w, h) = (area.x2 - area.x1, area.y2 - area.y1);
+    let can_split_x = w >= 2 * BSP_MIN_SIZE;
+    let can_split_y = h >= 2 * BSP_MIN_SIZE;
+
+    if depth == 0 || !(can_split_x || can_split_y) {
+        // keep a tile of rock to the neighbouring areas' rooms
+        let room_w = rng.gen_range(ROOM_MIN_SIZE..(cmp::min(ROOM_MAX_SIZE, w - 2) + 1));
+        let room_h = rng.gen_range(ROOM_MIN_SIZE..(cmp::min(ROOM_MAX_SIZE, h - 2) + 1));
+        let x = rng.gen_range((area.x1 + 1)..(area.x2 - room_w));
+        let y = rng.gen_range((area.y1 + 1)..(area.y2 - room_h));
+        let room = RectRoom::new(x, y, room_w, room_h);
+        create_room(room, map);
+        rooms.push(room);
+        return room;
+    }
+
+    // split across the longer side so the parts stay roughly square
+    let split_x = match (can_split_x, can_split_y) {
+        (true, false) => true,
+        (false, true) => false,
+        _ if w * 4 > h * 5 => true,
+        _ if h * 4 > w * 5 => false,
+        _ => rng.gen(),
+    };
+    let (first, second) = if split_x {
+        let x = rng.gen_range((area.x1 + BSP_MIN_SIZE)..(area.x2 - BSP_MIN_SIZE + 1));
+        (RectRoom { x2: x, ..area }, RectRoom { x1: x, ..area })
+    } else {
+        let y = rng.gen_range((area.y1 + BSP_MIN_SIZE)..(area.y2 - BSP_MIN_SIZE + 1));
+        (RectRoom { y2: y, ..area }, RectRoom { y1: y, ..area })
+    };
+
+    let first_room = bsp_split(first, depth - 1, map, rooms, rng);
+    let second_room = bsp_split(second, depth - 1, map, rooms, rng);
+    connect_rooms(first_room, second_room, map, rng);
+    if rng.gen() { first_room } else { second_room }
+}
+
+/// Digs an L-shaped tunnel between the centers of two rooms.
+fn connect_rooms(from: RectRoom, to: RectRoom, map: &mut GameMap, rng: &mut Pcg64) {
+    let (prev_x, prev_y) = from.center();
+    let (new_x, new_y) = to.center();
+    if rng.gen() {
+        create_h_tunnel(prev_x, new_x, prev_y, map);
+        create_v_tunnel(prev_y, new_y, new_x, map);
+    } else {
+        create_v_tunnel(prev_y, new_y, prev_x, map);
+        create_h_tunnel(prev_x, new_x, new_y, map);
+    }
+}
+
+/// Puts doors into some of the gaps the tunnels made in the room walls.
+fn add_doors(map: &mut GameMap, rooms: &[RectRoom], rng: &mut Pcg64) {
+    for room in rooms {
+        for (x, y) in room.walls() {
+            if is_doorway(x, y, map) && rng.gen_range(0..100) < DOOR_CHANCE {
+                let kind = if rng.gen_range(0..100) < SECRET_DOOR_CHANCE { TileKind::SecretDoor } else { TileKind::ClosedDoor };
+                map[x as usize][y as usize] = Tile::new(kind);
+            }
+        }
+    }
+}
+
+/// A floor tile with walls on two opposite sides.
+fn is_doorway(x: i32, y: i32, map: &GameMap) -> bool {
+    let wall = |x: i32, y: i32| map[x as usize][y as usize].kind == TileKind::Wall;
+    map[x as usize][y as usize].kind == TileKind::Floor &&
+        ((wall(x - 1, y) && wall(x + 1, y)) || (wall(x, y - 1) && wall(x, y + 1)))
+}
+
+/// Spreads patches of terrain over some of the areas. Patches that would cut off part of the level for
+/// creatures that walk are taken back.
+fn add_terrain(layout: &mut Layout, level: u32, rng: &mut Pcg64) {
+    let terrain_chances = [
+        (TileKind::DeepWater, 30),
+        (TileKind::Rubble, 30),
+        (TileKind::Chasm, from_dungeon_level(&[Transition { level: 3, value: 15 }], level)),
+        (TileKind::Lava, from_dungeon_level(&[Transition { level: 4, value: 20 }], level)),
+    ];
+    let terrain_choice = WeightedIndex::new(terrain_chances.iter().map(|chance| chance.1)).unwrap();
+
+    for area in &layout.areas {
+        if rng.gen_range(0..100) >= TERRAIN_CHANCE {
+            continue;
+        }
+        let kind = terrain_chances[terrain_choice.sample(rng)].0;
+
+        // grow the patch from a random tile of the area, keeping the start and the stairs clear
+        let area_tiles: HashSet<_> = area.iter().copied().collect();
+        let mut patch = vec![*area.choose(rng).unwrap()];
+        for _ in 0..TERRAIN_PATCH_SIZE * 4 {
+            let &(x, y) = patch.choose(rng).unwrap();
+            let next = *[(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)].choose(rng).unwrap();
+            if area_tiles.contains(&next) && !patch.contains(&next) {
+                patch.push(next);
+            }
+            if patch.len() == TERRAIN_PATCH_SIZE {
+                break;
+            }
+        }
+        patch.retain(|&tile| tile != layout.start && tile != layout.stairs);
+
+        let previous: Vec<Tile> = patch.iter().map(|&(x, y)| layout.map[x as usize][y as usize]).collect();
+        for &(x, y) in &patch {
+            layout.map[x as usize][y as usize] = Tile::new(kind);
+        }
+        if !kind.walkable() && !all_walkable_reached(layout) {
+            for (&(x, y), &tile) in patch.iter().zip(&previous) {
+                layout.map[x as usize][y as usize] = tile;
+            }
+        }
+    }
+}
+
+/// Whether every tile that can be walked on is reachable from the start.
+fn all_walkable_reached(layout: &Layout) -> bool {
+    let walkable = layout.map.iter().flatten().filter(|tile| tile.kind.walkable()).count();
+    flood_fill(layout.start, &layout.map).len() == walkable
+}
+
+/// Random rock smoothed into caves, keeping only the biggest connected one. The stairs end up as far from
+/// the player as the cave allows.
+fn caves(rng: &mut Pcg64) -> Layout {
+    loop {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for x in 1..MAP_WIDTH - 1 {
+            for y in 1..MAP_HEIGHT - 1 {
+                if rng.gen_range(0..100) >= CAVE_ROCK_CHANCE {
+                    map[x as usize][y as usize] = Tile::empty();
+                }
+            }
+        }
+        for _ in 0..CAVE_SMOOTHING_STEPS {
+            map = smooth_cave(&map);
+        }
+
+        // flood fill from every floor tile not yet reached, the biggest region wins
+        let mut reached = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        let mut cave: Vec<(i32, i32)> = vec![];
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                if !map[x as usize][y as usize].blocked() && !reached[x as usize][y as usize] {
+                    let region = flood_fill((x, y), &map);
+                    for &(rx, ry) in &region {
+                        reached[rx as usize][ry as usize] = true;
+                    }
+                    if region.len() > cave.len() {
+                        cave = region;
+                    }
+                }
+            }
+        }
+        if cave.len() < CAVE_MIN_FLOOR {
+            continue;
+        }
+
+        // fill the disconnected pockets
+        let mut cave_map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for &(x, y) in &cave {
+            cave_map[x as usize][y as usize] = Tile::empty();
+        }
+
+        let start = *cave.choose(rng).unwrap();
+        // flood fill reaches the farthest tile last
+        let stairs = *flood_fill(start, &cave_map).last().unwrap();
+
+        let mut areas = vec![];
+        for sector_x in (0..MAP_WIDTH).step_by(CAVE_SECTOR_WIDTH as usize) {
+            for sector_y in (0..MAP_HEIGHT).step_by(CAVE_SECTOR_HEIGHT as usize) {
+                let area: Vec<_> = cave.iter()
+                    .copied()
+                    .filter(|&(x, y)| {
+                        (sector_x..sector_x + CAVE_SECTOR_WIDTH).contains(&x) &&
+                            (sector_y..sector_y + CAVE_SECTOR_HEIGHT).contains(&y)
+                    })
+                    .collect();
+                if area.len() >= CAVE_SECTOR_MIN_FLOOR {
+                    areas.push(area);
+                }
+            }
+        }
+
+        return Layout { map: cave_map, areas, start, stairs };
+    }
+}
+
+/// One cellular automaton step: a tile turns to rock when most of the tiles around it are rock.
+fn smooth_cave(map: &GameMap) -> GameMap {
+    let mut smoothed = map.clone();
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            let rock = (x - 1..=x + 1)
+                .flat_map(|nx| (y - 1..=y + 1).map(move |ny| (nx, ny)))
+                .filter(|&(nx, ny)| map[nx as usize][ny as usize].blocked())
+                .count();
+            smoothed[x as usize][y as usize] = if rock >= 5 { Tile::wall() } else { Tile::empty() };
+        }
+    }
+    smoothed
+}
+
+/// Walkable tiles reachable from `start` without moving diagonally, nearest first.
+fn flood_fill(start: (i32, i32), map: &GameMap) -> Vec<(i32, i32)> {
+    let mut seen = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut queue = VecDeque::from([start]);
+    let mut region = vec![];
+    seen[start.0 as usize][start.1 as usize] = true;
+    while let Some((x, y)) = queue.pop_front() {
+        region.push((x, y));
+        for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            let in_map = nx >= 0 && ny >= 0 && nx < MAP_WIDTH && ny < MAP_HEIGHT;
+            if in_map && !seen[nx as usize][ny as usize] && map[nx as usize][ny as usize].kind.walkable() {
+                seen[nx as usize][ny as usize] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    region
 }
 
 fn create_room(room: RectRoom, map: &mut GameMap) {
@@ -91,82 +477,185 @@ fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut GameMap) {
     }
 }
 
-fn place_objects(room: RectRoom, map: &GameMap, objects: &mut Vec<Object>) {
-    let num_monsters = rand::thread_rng().gen_range(0..MAX_ROOM_MONSTERS + 1);
+fn place_objects(area: &[(i32, i32)], map: &GameMap, world: &mut World, level: u32, content: &Content, rng: &mut Pcg64) {
+    let max_monsters = from_dungeon_level(&[
+        Transition { level: 1, value: 2 },
+        Transition { level: 4, value: 3 },
+        Transition { level: 6, value: 5 },
+    ], level);
+    let monster_weights = content.monsters.iter().map(|monster| from_dungeon_level(&monster.spawn, level));
+    // every weight may be 0 at this depth, then nothing spawns
+    let monster_choice = WeightedIndex::new(monster_weights).ok();
+
+    let num_monsters = rng.gen_range(0..max_monsters + 1);
 
     for _ in 0..num_monsters {
-        let x = rand::thread_rng().gen_range(room.x1 + 1..room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1..room.y2);
-        if !is_blocked(x, y, map, objects) {
-            let mut monster = if rand::thread_rng().gen_ratio(4, 5) {
-                let mut ork = Object::new(x, y, 'o', "ork", colors::DESATURATED_GREEN, true);
-                ork.fighter = Some(Fighter { max_hp: 10, hp: 10, defense: 0, power: 3, on_death: Monster });
-                ork.ai = Some(Ai::Basic);
-                ork
-            } else {
-                let mut troll = Object::new(x, y, 'T', "troll", colors::DARKER_GREEN, true);
-                troll.fighter = Some(Fighter { max_hp: 16, hp: 16, defense: 1, power: 4, on_death: Monster });
-                troll.ai = Some(Ai::Basic);
-                troll
-            };
-            monster.alive = true;
-            objects.push(monster)
-        }
-    }
-
-    let num_items = rand::thread_rng().gen_range(0..MAX_ROOM_ITEMS + 1);
+        let &(x, y) = area.choose(rng).unwrap();
+        if let Some(monster_choice) = &monster_choice {
+            if is_free_floor(x, y, map, world) {
+                content.monsters[monster_choice.sample(rng)].spawn(x, y, world);
+            }
+        }
+    }
+
+    let max_items = from_dungeon_level(&[
+        Transition { level: 1, value: 1 },
+        Transition { level: 4, value: 2 },
+    ], level);
+
+    let item_weights = content.items.iter().map(|item| from_dungeon_level(&item.spawn, level));
+    let item_choice = WeightedIndex::new(item_weights).ok();
+
+    let num_items = rng.gen_range(0..max_items + 1);
 
     for _ in 0..num_items {
-        let x = rand::thread_rng().gen_range(room.x1 + 1..room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1..room.y2);
+        let &(x, y) = area.choose(rng).unwrap();
 
-        if !is_blocked(x, y, map, objects) {
-            let mut object = Object::new(x, y, '!', "healing potion", VIOLET, false);
-            object.item = Some(Item::Heal);
-            objects.push(object);
+        if let Some(item_choice) = &item_choice {
+            if is_free_floor(x, y, map, world) {
+                content.items[item_choice.sample(rng)].spawn(x, y, world);
+            }
         }
     }
 }
 
-pub fn draw_map(game: &mut Game, con: &mut dyn Console, fov_map: &Map) {
+/// Maybe hides a trap somewhere in the area, never on the start or the stairs.
+fn place_trap(area: &[(i32, i32)], layout: &Layout, world: &mut World, level: u32, rng: &mut Pcg64) {
+    if rng.gen_range(0..100) >= TRAP_CHANCE {
+        return;
+    }
+    let trap_chances = [
+        (TrapKind::Dart, 40),
+        (TrapKind::Pit, 30),
+        (TrapKind::Alarm, from_dungeon_level(&[Transition { level: 2, value: 20 }], level)),
+        (TrapKind::Teleport, from_dungeon_level(&[Transition { level: 3, value: 15 }], level)),
+    ];
+    let trap_choice = WeightedIndex::new(trap_chances.iter().map(|chance| chance.1)).unwrap();
+
+    let &(x, y) = area.choose(rng).unwrap();
+    if (x, y) != layout.start && (x, y) != layout.stairs && is_free_floor(x, y, &layout.map, world) {
+        spawn_trap(trap_chances[trap_choice.sample(rng)].0, x, y, world);
+    }
+}
+
+pub fn draw_map(game: &mut Game, renderer: &mut dyn Renderer, fov_map: &Map) {
     for y in 0..MAP_HEIGHT {
         for x in 0..MAP_WIDTH {
             let visible = fov_map.is_in_fov(x, y);
-            let wall = game.map[x as usize][y as usize].block_sight;
-            let color = match (visible, wall) {
-                (false, true) => COLOR_DARK_WALL,
-                (false, false) => COLOR_DARK_GROUND,
-                (true, true) => COLOR_LIGHT_WALL,
-                (true, false) => COLOR_LIGHT_GROUND,
-            };
-
-            let explored = &mut game.map[x as usize][y as usize].explored;
+            let tile = &mut game.map[x as usize][y as usize];
             if visible {
-                *explored = true;
+                tile.explored = true;
+            }
+
+            if tile.explored {
+                let look = tile.kind.look();
+                let (color, background) = if visible {
+                    (look.color, look.background)
+                } else {
+                    (look.dark_color, look.dark_background)
+                };
+                renderer.set_background(x, y, background);
+                if look.glyph != ' ' {
+                    renderer.put_char(x, y, look.glyph, color);
+                }
             }
+        }
+    }
+}
 
-            if *explored {
-                con.set_char_background(x, y, color, BackgroundFlag::Set);
+/// Finds each secret door next to the tile with a chance, turning it into a closed door. Returns how many were found.
+pub fn search(x: i32, y: i32, map: &mut GameMap, rng: &mut Pcg64) -> usize {
+    let mut found = 0;
+    for nx in (x - 1)..=(x + 1) {
+        for ny in (y - 1)..=(y + 1) {
+            let tile = &mut map[nx as usize][ny as usize];
+            if tile.kind == TileKind::SecretDoor && rng.gen_range(0..100) < SEARCH_CHANCE {
+                tile.kind = TileKind::ClosedDoor;
+                found += 1;
             }
         }
     }
+    found
 }
 
-pub fn is_blocked(x: i32, y: i32, map: &GameMap, objects: &[Object]) -> bool {
-    if map[x as usize][y as usize].blocked {
+/// Whether `mover` cannot step onto the tile: it is solid, taken by another creature, or terrain the mover
+/// keeps out of.
+pub fn is_blocked(x: i32, y: i32, mover: Entity, map: &GameMap, world: &World) -> bool {
+    let tile = map[x as usize][y as usize];
+    if tile.blocked() || !can_enter(mover, tile.kind, world) {
         return true;
     }
 
-    objects.iter().any(|o| o.blocks && o.position() == (x, y))
+    world.entities_at(x, y).any(|entity| world.blockers.contains(entity))
+}
+
+/// Plain floor with nothing blocking on it, where monsters and items are placed.
+fn is_free_floor(x: i32, y: i32, map: &GameMap, world: &World) -> bool {
+    map[x as usize][y as usize].kind == TileKind::Floor &&
+        !world.entities_at(x, y).any(|entity| world.blockers.contains(entity))
 }
 
 impl Tile {
+    pub fn new(kind: TileKind) -> Self {
+        Self { kind, explored: false }
+    }
+
     pub fn empty() -> Self {
-        Self { blocked: false, block_sight: false, explored: false }
+        Self::new(TileKind::Floor)
     }
 
     pub fn wall() -> Self {
-        Self { blocked: true, block_sight: true, explored: false }
+        Self::new(TileKind::Wall)
+    }
+
+    pub fn blocked(&self) -> bool {
+        matches!(self.kind, TileKind::Wall | TileKind::ClosedDoor | TileKind::SecretDoor)
+    }
+
+    pub fn block_sight(&self) -> bool {
+        matches!(self.kind, TileKind::Wall | TileKind::ClosedDoor | TileKind::SecretDoor)
+    }
+}
+
+impl TileKind {
+    /// Crossed on foot without harm, opening doors on the way.
+    pub fn walkable(self) -> bool {
+        use TileKind::*;
+        matches!(self, Floor | OpenDoor | ClosedDoor | SecretDoor | Rubble)
+    }
+
+    fn look(self) -> TileLook {
+        use TileKind::*;
+        let ground = TileLook {
+            glyph: ' ',
+            color: WHITE,
+            dark_color: WHITE,
+            background: COLOR_LIGHT_GROUND,
+            dark_background: COLOR_DARK_GROUND,
+        };
+        match self {
+            Floor => ground,
+            // secret doors pass for walls
+            Wall | SecretDoor => TileLook { background: COLOR_LIGHT_WALL, dark_background: COLOR_DARK_WALL, ..ground },
+            ClosedDoor => TileLook { glyph: '+', color: COLOR_LIGHT_DOOR, dark_color: COLOR_DARK_DOOR, ..ground },
+            OpenDoor => TileLook { glyph: '\'', color: COLOR_LIGHT_DOOR, dark_color: COLOR_DARK_DOOR, ..ground },
+            DeepWater => TileLook {
+                glyph: '~',
+                color: LIGHT_BLUE,
+                dark_color: DARKER_GREY,
+                background: COLOR_LIGHT_WATER,
+                dark_background: COLOR_DARK_WATER,
+            },
+            Lava => TileLook {
+                glyph: '~',
+                color: YELLOW,
+                dark_color: DARKER_GREY,
+                background: DARK_RED,
+                dark_background: COLOR_DARK_LAVA,
+            },
+            Chasm => TileLook { background: BLACK, dark_background: BLACK, ..ground },
+            Rubble => TileLook { glyph: ':', color: GREY, dark_color: DARKER_GREY, ..ground },
+        }
     }
 }
 
@@ -187,6 +676,21 @@ impl RectRoom {
         ((self.x1 + self.x2) / 2, (self.y1 + self.y2) / 2)
     }
 
+    /// The tiles of the walls around the floor.
+    pub fn walls(&self) -> Vec<(i32, i32)> {
+        (self.x1..=self.x2)
+            .flat_map(|x| (self.y1..=self.y2).map(move |y| (x, y)))
+            .filter(|&(x, y)| x == self.x1 || x == self.x2 || y == self.y1 || y == self.y2)
+            .collect()
+    }
+
+    /// The tiles inside the walls.
+    pub fn floor(&self) -> Vec<(i32, i32)> {
+        ((self.x1 + 1)..self.x2)
+            .flat_map(|x| ((self.y1 + 1)..self.y2).map(move |y| (x, y)))
+            .collect()
+    }
+
     pub fn intersects_with(&self, other: &RectRoom) -> bool {
         (self.x1 <= other.x2) &&
             (self.x2 >= other.x1) &&
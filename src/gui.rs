@@ -1,10 +1,14 @@
-use tcod::{BackgroundFlag, Color, Console, TextAlignment};
-use tcod::colors::{BLACK, DARKER_RED, LIGHT_GREY, LIGHT_RED, WHITE};
-use tcod::console::Offscreen;
-use tcod::input::Mouse;
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use tcod::{Color, TextAlignment};
+use tcod::colors::{DARKER_RED, DARKER_VIOLET, LIGHT_GREY, LIGHT_RED, LIGHT_VIOLET, WHITE};
 use tcod::map::Map as FovMap;
-use crate::{PLAYER_ID, SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::object::Object;
+use crate::{Game, PLAYER_ID, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::backend::{Mouse, Renderer, text_height};
+use crate::combat::{level_up_xp, max_hp};
+use crate::components::Level;
+use crate::ecs::World;
+use crate::traps::is_hidden;
 
 const BAR_WIDTH: i32 = 20;
 pub const PANEL_HEIGHT: i32 = 7;
@@ -14,75 +18,163 @@ const MSG_X: i32 = BAR_WIDTH + 2;
 const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
 const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
 
+/// Messages retained by default, older ones are dropped.
+pub const DEFAULT_MESSAGE_LIMIT: usize = 500;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    pub color: Color,
+    /// How many times in a row the message was added.
+    pub count: u32,
+}
+
+impl Message {
+    /// The text with the repeat count, e.g. "Ork attacks you for 3 hit points. x4".
+    pub fn display_text(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Messages {
-    messages: Vec<(String, Color)>,
+    messages: VecDeque<Message>,
+    /// A setting rather than part of the game, so it is not saved; `set_limit` applies it to a loaded game.
+    #[serde(skip, default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    DEFAULT_MESSAGE_LIMIT
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Messages {
     pub fn new() -> Self {
-        Self { messages: vec![] }
+        Self::with_limit(DEFAULT_MESSAGE_LIMIT)
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        Self { messages: VecDeque::new(), limit }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.truncate();
     }
 
     pub fn add<T: Into<String>>(&mut self, message: T, color: Color) {
-        self.messages.push((message.into(), color))
+        let text = message.into();
+        if let Some(last) = self.messages.back_mut() {
+            if last.text == text && last.color == color {
+                last.count += 1;
+                return;
+            }
+        }
+        self.messages.push_back(Message { text, color, count: 1 });
+        self.truncate();
     }
 
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item=&(String, Color)> {
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item=&Message> {
         self.messages.iter()
     }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    fn truncate(&mut self) {
+        while self.messages.len() > self.limit {
+            self.messages.pop_front();
+        }
+    }
 }
 
-pub fn draw_gui(panel: &mut Offscreen, objects: &[Object], messages: &Messages, mouse: &Mouse, fov: &FovMap) {
-    panel.set_default_background(BLACK);
-    panel.clear();
+pub fn draw_gui(renderer: &mut dyn Renderer, world: &World, game: &Game, mouse: &Mouse, fov: &FovMap) {
+    let hp = world.fighters.get(PLAYER_ID).map_or(0, |f| f.hp);
+    let max_hp = max_hp(PLAYER_ID, game, world);
+
+    render_bar(renderer, 1, PANEL_Y + 1, BAR_WIDTH, "HP", hp, max_hp, LIGHT_RED, DARKER_RED);
 
-    let hp = objects[PLAYER_ID].fighter.map_or(0, |f| f.hp);
-    let max_hp = objects[PLAYER_ID].fighter.map_or(0, |f| f.max_hp);
+    let level = world.get::<Level>(PLAYER_ID).map_or(1, |l| l.0);
+    let xp = world.fighters.get(PLAYER_ID).map_or(0, |f| f.xp);
+    render_bar(renderer, 1, PANEL_Y + 2, BAR_WIDTH, "XP", xp, level_up_xp(level), LIGHT_VIOLET, DARKER_VIOLET);
 
-    render_bar(panel, 1, 1, BAR_WIDTH, "HP", hp, max_hp, LIGHT_RED, DARKER_RED);
+    renderer.print(1, PANEL_Y + 3, &format!("Player level: {}", level), LIGHT_GREY, TextAlignment::Left);
+    renderer.print(1, PANEL_Y + 4, &format!("Dungeon level: {}", game.dungeon_level), LIGHT_GREY, TextAlignment::Left);
+    renderer.print(1, PANEL_Y + 5, &format!("Seed: {}", game.seed), LIGHT_GREY, TextAlignment::Left);
+    render_effects(renderer, 1, PANEL_Y + 6, world);
 
-    render_messages(panel, messages);
+    render_messages(renderer, &game.messages);
 
-    render_mouse(panel, mouse, objects, fov);
+    render_mouse(renderer, mouse, world, fov);
 }
 
-fn render_bar(panel: &mut Offscreen, x: i32, y: i32, total_width: i32, name: &str, value: i32, max: i32, bar_color: Color, back_color: Color) {
+#[allow(clippy::too_many_arguments)]
+fn render_bar(renderer: &mut dyn Renderer, x: i32, y: i32, total_width: i32, name: &str, value: i32, max: i32, bar_color: Color, back_color: Color) {
     let bar_width = (value as f32 / max as f32 * total_width as f32) as i32;
 
-    panel.set_default_background(back_color);
-    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
+    renderer.fill_background(x, y, total_width, 1, back_color);
 
-    panel.set_default_background(bar_color);
     if bar_width > 0 {
-        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
+        renderer.fill_background(x, y, bar_width, 1, bar_color);
     }
 
-    panel.set_default_foreground(WHITE);
-    panel.print_ex(x + total_width / 2, y, BackgroundFlag::None, TextAlignment::Center, &format!("{}: {}/{}", name, value, max));
+    renderer.print(x + total_width / 2, y, &format!("{}: {}/{}", name, value, max), WHITE, TextAlignment::Center);
+}
+
+/// A tag for every status effect the player is under, as many as fit under the bars.
+fn render_effects(renderer: &mut dyn Renderer, x: i32, y: i32, world: &World) {
+    let effects = match world.status_effects.get(PLAYER_ID) {
+        Some(effects) => effects,
+        None => return,
+    };
+    let mut tag_x = x;
+    for effect in &effects.0 {
+        let tag = effect.kind.tag();
+        if tag_x + tag.len() as i32 > x + BAR_WIDTH {
+            break;
+        }
+        renderer.print(tag_x, y, tag, effect.kind.color(), TextAlignment::Left);
+        tag_x += tag.len() as i32 + 1;
+    }
 }
 
-fn render_messages(panel: &mut Offscreen, messages: &Messages) {
+fn render_messages(renderer: &mut dyn Renderer, messages: &Messages) {
     let mut y = MSG_HEIGHT as i32;
-    for &(ref msg, color) in messages.iter().rev() {
-        let msg_height = panel.get_height_rect(MSG_X, y, MSG_WIDTH, 0, msg);
+    for message in messages.iter().rev() {
+        let msg = message.display_text();
+        let msg_height = text_height(&msg, MSG_WIDTH);
         y -= msg_height;
         if y < 0 { break; }
-        panel.set_default_foreground(color);
-        panel.print_rect(MSG_X, y, MSG_WIDTH, 0, msg);
+        renderer.print_rect(MSG_X, PANEL_Y + y, MSG_WIDTH, &msg, message.color);
     }
 }
 
-fn render_mouse(panel: &mut Offscreen, mouse: &Mouse, objects: &[Object], fov: &FovMap) {
-    panel.set_default_foreground(LIGHT_GREY);
-    panel.print_ex(1, 0, BackgroundFlag::None, TextAlignment::Left, get_names_under_mouse(mouse, objects, fov));
+fn render_mouse(renderer: &mut dyn Renderer, mouse: &Mouse, world: &World, fov: &FovMap) {
+    renderer.print(1, PANEL_Y, &get_names_under_mouse(mouse, world, fov), LIGHT_GREY, TextAlignment::Left);
 }
 
-fn get_names_under_mouse(mouse: &Mouse, objects: &[Object], fov: &FovMap) -> String {
-    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+fn get_names_under_mouse(mouse: &Mouse, world: &World, fov: &FovMap) -> String {
+    let (x, y) = (mouse.x, mouse.y);
 
-    let names = objects.iter()
-        .filter(|o| o.position() == (x, y) && fov.is_in_fov(o.position().0, o.position().1))
-        .map(|o| o.name.clone())
+    let names = world.entities_at(x, y)
+        .filter(|_| fov.is_in_fov(x, y))
+        .filter(|&entity| !is_hidden(entity, world))
+        .map(|entity| world.name(entity).to_string())
         .collect::<Vec<_>>();
 
     names.join(", ")
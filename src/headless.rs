@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use tcod::Color;
+use tcod::colors::{BLACK, WHITE};
+use crate::backend::{Event, Input, Key, KeyCode, Renderer};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { glyph: ' ', foreground: WHITE, background: BLACK }
+    }
+}
+
+/// Renders into an in-memory grid of cells, so the game runs without a display.
+pub struct HeadlessRenderer {
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+    frames: usize,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self { width, height, cells: vec![Cell::default(); (width * height) as usize], frames: 0 }
+    }
+
+    pub fn cell(&self, x: i32, y: i32) -> Cell {
+        self.cells[self.index(x, y).expect("cell out of bounds")]
+    }
+
+    /// The glyphs of one row as a string.
+    pub fn row(&self, y: i32) -> String {
+        (0..self.width).map(|x| self.cell(x, y).glyph).collect()
+    }
+
+    pub fn contains_text(&self, text: &str) -> bool {
+        (0..self.height).any(|y| self.row(y).contains(text))
+    }
+
+    /// Number of times the screen was flushed.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl Renderer for HeadlessRenderer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|cell| *cell = Cell::default());
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: Color) {
+        if let Some(index) = self.index(x, y) {
+            self.cells[index].glyph = glyph;
+            self.cells[index].foreground = color;
+        }
+    }
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color) {
+        if let Some(index) = self.index(x, y) {
+            self.cells[index].background = color;
+        }
+    }
+
+    fn flush(&mut self) {
+        self.frames += 1;
+    }
+}
+
+/// Replays a scripted list of events. Once the script runs out the input is finished, which closes the game like
+/// a closed window; a menu still waiting for a key gets Escape and backs out.
+#[derive(Default)]
+pub struct HeadlessInput {
+    events: VecDeque<Event>,
+}
+
+impl HeadlessInput {
+    pub fn new<I: IntoIterator<Item=Event>>(events: I) -> Self {
+        Self { events: events.into_iter().collect() }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    pub fn push_keys(&mut self, keys: &[Key]) {
+        self.events.extend(keys.iter().map(|&key| Event::Key(key)));
+    }
+}
+
+impl Input for HeadlessInput {
+    fn check_for_event(&mut self) -> Option<Event> {
+        Some(self.events.pop_front().unwrap_or(Event::Key(Key::new(KeyCode::Escape))))
+    }
+
+    fn wait_for_keypress(&mut self) -> Key {
+        loop {
+            match self.events.pop_front() {
+                Some(Event::Key(key)) => return key,
+                Some(Event::Mouse(_)) => continue,
+                None => return Key::new(KeyCode::Escape),
+            }
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
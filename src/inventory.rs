@@ -1,57 +1,382 @@
-use tcod::colors::{GREEN, LIGHT_VIOLET, RED, WHITE};
-use crate::{Game, Object, PLAYER_ID};
+use serde::{Deserialize, Serialize};
+use tcod::colors::{GREEN, LIGHT_BLUE, LIGHT_CYAN, LIGHT_GREEN, LIGHT_VIOLET, LIGHT_YELLOW, ORANGE, RED, WHITE, YELLOW};
+use crate::{Game, PLAYER_ID};
+use crate::combat::{defense, gain_xp, heal, max_hp, take_damage};
+use crate::components::{Position, Quantity, Slot};
+use crate::ecs::{Entity, World};
+use crate::effects::{apply_effect, EffectKind};
+use crate::engine::Ui;
+use crate::gui::Messages;
+use crate::movement::distance;
+use crate::ranged::{fire, fly};
+use crate::targeting::{target_line, target_monster, target_tile};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// What using the item does.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Item {
     Heal,
+    Lightning,
+    Confuse,
+    Fireball,
+    Haste,
+    Regeneration,
+    /// Shot from the launcher made for it.
+    Ammo,
+    /// Worn, the bonuses come from its `Equipment`.
+    Equip,
 }
 
 enum UseResult {
     UsedUp,
+    UsedAndKept,
     Cancelled,
 }
 
+const INVENTORY_SIZE: usize = 26;
+
 const HEAL_AMOUNT: i32 = 4;
+const LIGHTNING_DAMAGE: i32 = 40;
+const LIGHTNING_RANGE: i32 = 5;
+const CONFUSE_RANGE: i32 = 8;
+const CONFUSE_NUM_TURNS: i32 = 10;
+const HASTE_NUM_TURNS: i32 = 20;
+const REGENERATION_NUM_TURNS: i32 = 15;
+const FIREBALL_RADIUS: i32 = 3;
+const FIREBALL_DAMAGE: i32 = 25;
+const THROW_RANGE: i32 = 8;
+/// Damage of a thrown item hitting a creature, weapons add their power bonus.
+const THROW_DAMAGE: i32 = 3;
+
+pub fn pick_item_up(item: Entity, game: &mut Game, world: &mut World) {
+    let stack = game.inventory.iter().position(|&carried| stacks_with(carried, item, world));
+    if stack.is_none() && game.inventory.len() >= INVENTORY_SIZE {
+        game.messages.add(format!("Your inventory is full, cannot pick up {}", world.name(item)), RED);
+    } else {
+        game.messages.add(format!("You picked up {}!", describe(item, world)), GREEN);
+        world.remove::<Position>(item);
+        match stack {
+            Some(stack) => {
+                let carried = game.inventory[stack];
+                let quantity = world.quantity(carried) + world.quantity(item);
+                world.insert(carried, Quantity(quantity));
+                world.despawn(item);
+            }
+            None => game.inventory.push(item),
+        }
+    }
+}
+
+/// Puts the whole stack down where the player stands.
+pub fn drop_item(inventory_id: usize, game: &mut Game, world: &mut World) {
+    let item = game.inventory.remove(inventory_id);
+    if world.equipment.contains(item) {
+        dequip(item, game, world);
+    }
+    let (x, y) = world.player_position();
+    world.set_position(item, x, y);
+    game.messages.add(format!("You dropped {}.", describe(item, world)), YELLOW);
+}
+
+/// Throws one item of the stack at a tile. It flies until it hits a wall or a creature, potions shatter,
+/// everything else lands on the floor. Returns whether the item was thrown.
+pub fn throw_item(inventory_id: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> bool {
+    game.messages.add(
+        format!("Throw the {} where? Pick a tile with the mouse or the arrow keys and Enter, or Escape to cancel.", world.name(game.inventory[inventory_id])),
+        LIGHT_CYAN,
+    );
+    let target = match target_line(ui, game, world, Some(THROW_RANGE as f32)) {
+        Some(tile_pos) => tile_pos,
+        None => {
+            game.messages.add("Cancelled", WHITE);
+            return false;
+        }
+    };
+
+    let item = take_one(inventory_id, game, world);
+    if world.equipment.get(item).is_some_and(|e| e.equipped) {
+        dequip(item, game, world);
+    }
+    let from = world.player_position();
+    let (path, hit) = fly(from, target, &game.map, world);
+    let (x, y) = path.last().copied().unwrap_or(from);
+    let name = world.name(item).to_string();
+
+    if world.potions.contains(item) {
+        match hit {
+            Some(target) => splash(item, target, game, world),
+            None => game.messages.add(format!("The {} shatters.", name), WHITE),
+        }
+        world.despawn(item);
+        return true;
+    }
+
+    match hit {
+        Some(target) => {
+            let power_bonus = world.equipment.get(item).map_or(0, |e| e.power_bonus);
+            let damage = THROW_DAMAGE + power_bonus - defense(target, game, world);
+            if damage > 0 {
+                game.messages.add(format!("The {} hits the {} for {} hit points.", name, world.name(target), damage), WHITE);
+                if let Some(xp) = take_damage(target, damage, game, world) {
+                    gain_xp(PLAYER_ID, xp, world);
+                }
+            } else {
+                game.messages.add(format!("The {} bounces off the {}.", name, world.name(target)), WHITE);
+            }
+        }
+        None => game.messages.add(format!("The {} lands on the floor.", name), WHITE),
+    }
+    world.set_position(item, x, y);
+    true
+}
 
-pub fn pick_item_up(object_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
-    if game.inventory.len() >= 26 {
-        game.messages.add(format!("Your inventory is full, cannot pick up {}", objects[object_id].name), RED);
+/// A thrown potion breaks over the creature and works on it as if it had drunk it.
+fn splash(potion: Entity, target: Entity, game: &mut Game, world: &mut World) {
+    let (name, target_name) = (world.name(potion).to_string(), world.name(target).to_string());
+    match world.items.get(potion) {
+        Some(Item::Heal) => {
+            game.messages.add(format!("The {} shatters over the {}, who looks healthier.", name, target_name), LIGHT_VIOLET);
+            heal(target, HEAL_AMOUNT, game, world);
+        }
+        Some(Item::Haste) => {
+            game.messages.add(format!("The {} shatters over the {}, who speeds up.", name, target_name), LIGHT_YELLOW);
+            apply_effect(target, EffectKind::Hasted, HASTE_NUM_TURNS, world);
+        }
+        Some(Item::Regeneration) => {
+            game.messages.add(format!("The {} shatters over the {}, whose wounds start to close.", name, target_name), LIGHT_GREEN);
+            apply_effect(target, EffectKind::Regenerating, REGENERATION_NUM_TURNS, world);
+        }
+        _ => game.messages.add(format!("The {} shatters over the {}.", name, target_name), WHITE),
+    }
+}
+
+/// Identical items that can't be equipped share one inventory slot.
+fn stacks_with(first: Entity, second: Entity, world: &World) -> bool {
+    world.items.contains(first) && world.items.get(first) == world.items.get(second)
+        && world.name(first) == world.name(second)
+        && !world.equipment.contains(first) && !world.equipment.contains(second)
+}
+
+/// The name with the size of the stack, if there is more than one.
+pub fn display_name(item: Entity, world: &World) -> String {
+    match world.quantity(item) {
+        1 => world.name(item).to_string(),
+        quantity => format!("{} (x{})", world.name(item), quantity),
+    }
+}
+
+/// "a healing potion" for a single item, "healing potion (x3)" for a stack.
+fn describe(item: Entity, world: &World) -> String {
+    if world.quantity(item) > 1 {
+        display_name(item, world)
+    } else {
+        format!("a {}", world.name(item))
+    }
+}
+
+/// Takes one item of the stack out of the inventory, the whole stack if it was the last one.
+pub fn take_one(inventory_id: usize, game: &mut Game, world: &mut World) -> Entity {
+    let stack = game.inventory[inventory_id];
+    let quantity = world.quantity(stack);
+    if quantity > 1 {
+        world.insert(stack, Quantity(quantity - 1));
+        let item = world.duplicate(stack);
+        world.remove::<Quantity>(item);
+        item
     } else {
-        let item = objects.swap_remove(object_id);
-        game.messages.add(format!("You picked up a {}!", item.name), GREEN);
-        game.inventory.push(item);
+        game.inventory.remove(inventory_id)
     }
 }
 
-pub fn use_item(inventory_id: usize, game: &mut Game, objects: &mut [Object]) {
+/// Returns whether the item was used, cancelling takes no time.
+pub fn use_item(inventory_id: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> bool {
     use Item::*;
 
-    if let Some(item) = game.inventory[inventory_id].item {
+    if let Some(&item) = world.items.get(game.inventory[inventory_id]) {
         let on_use = match item {
             Heal => cast_heal,
+            Lightning => cast_lightning,
+            Confuse => cast_confuse,
+            Fireball => cast_fireball,
+            Haste => cast_haste,
+            Regeneration => cast_regeneration,
+            Ammo => shoot_ammo,
+            Equip => toggle_equipment,
         };
-        match on_use(inventory_id, game, objects) {
+        match on_use(inventory_id, ui, game, world) {
             UseResult::UsedUp => {
-                game.inventory.remove(inventory_id);
+                let used = take_one(inventory_id, game, world);
+                world.despawn(used);
+                true
             }
+            UseResult::UsedAndKept => true,
             UseResult::Cancelled => {
                 game.messages.add("Cancelled", WHITE);
+                false
             }
         }
     } else {
-        game.messages.add(format!("The {} cannot be used.", game.inventory[inventory_id].name), WHITE);
+        game.messages.add(format!("The {} cannot be used.", world.name(game.inventory[inventory_id])), WHITE);
+        false
     }
 }
 
-fn cast_heal(_inventory_id: usize, game: &mut Game, objects: &mut [Object]) -> UseResult {
-    if let Some(fighter) = objects[PLAYER_ID].fighter {
-        if fighter.hp == fighter.max_hp {
+fn cast_heal(_inventory_id: usize, _ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    if let Some(&fighter) = world.fighters.get(PLAYER_ID) {
+        if fighter.hp >= max_hp(PLAYER_ID, game, world) {
             game.messages.add("You are already at full hp.", RED);
             return UseResult::Cancelled;
         }
         game.messages.add("Healted.", LIGHT_VIOLET);
-        objects[PLAYER_ID].heal(HEAL_AMOUNT);
+        heal(PLAYER_ID, HEAL_AMOUNT, game, world);
         return UseResult::UsedUp;
     }
     UseResult::Cancelled
 }
+
+fn cast_lightning(_inventory_id: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    let monster = closest_monster(ui, world, LIGHTNING_RANGE);
+    if let Some(monster) = monster {
+        game.messages.add(
+            format!("A lightning bolt strikes the {} with a loud thunder! The damage is {} hit points.", world.name(monster), LIGHTNING_DAMAGE),
+            LIGHT_BLUE,
+        );
+        if let Some(xp) = take_damage(monster, LIGHTNING_DAMAGE, game, world) {
+            gain_xp(PLAYER_ID, xp, world);
+        }
+        UseResult::UsedUp
+    } else {
+        game.messages.add("No enemy is close enough to strike.", RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_confuse(_inventory_id: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    game.messages.add("Select an enemy to confuse with the mouse or the arrow keys and Enter, or Escape to cancel.", LIGHT_CYAN);
+    let monster = target_monster(ui, game, world, Some(CONFUSE_RANGE as f32));
+    if let Some(monster) = monster {
+        apply_effect(monster, EffectKind::Confused, CONFUSE_NUM_TURNS, world);
+        game.messages.add(format!("The eyes of {} look vacant, as he starts to stumble around!", world.name(monster)), LIGHT_GREEN);
+        UseResult::UsedUp
+    } else {
+        game.messages.add("No enemy is targeted.", RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_haste(_inventory_id: usize, _ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    game.messages.add("You feel yourself speed up.", LIGHT_YELLOW);
+    apply_effect(PLAYER_ID, EffectKind::Hasted, HASTE_NUM_TURNS, world);
+    UseResult::UsedUp
+}
+
+fn cast_regeneration(_inventory_id: usize, _ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    game.messages.add("Your wounds start to close by themselves.", LIGHT_GREEN);
+    apply_effect(PLAYER_ID, EffectKind::Regenerating, REGENERATION_NUM_TURNS, world);
+    UseResult::UsedUp
+}
+
+fn shoot_ammo(inventory_id: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    // the shot already took the ammunition out of the stack
+    if fire(inventory_id, ui, game, world) { UseResult::UsedAndKept } else { UseResult::Cancelled }
+}
+
+fn cast_fireball(_inventory_id: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    game.messages.add("Select a target tile for the fireball with the mouse or the arrow keys and Enter, or Escape to cancel.", LIGHT_CYAN);
+    let (x, y) = match target_tile(ui, game, world, None, FIREBALL_RADIUS) {
+        Some(tile_pos) => tile_pos,
+        None => return UseResult::Cancelled,
+    };
+    game.messages.add(format!("The fireball explodes, burning everything within {} tiles!", FIREBALL_RADIUS), ORANGE);
+
+    let burned: Vec<Entity> = world.fighters.iter()
+        .map(|(entity, _)| entity)
+        .filter(|&entity| world.position(entity).is_some_and(|position| distance(position, (x, y)) <= FIREBALL_RADIUS as f32))
+        .collect();
+    let mut xp_to_gain = 0;
+    for entity in burned {
+        game.messages.add(format!("The {} gets burned for {} hit points.", world.name(entity), FIREBALL_DAMAGE), ORANGE);
+        if let Some(xp) = take_damage(entity, FIREBALL_DAMAGE, game, world) {
+            // the player gets no experience for burning themself
+            if entity != PLAYER_ID {
+                xp_to_gain += xp;
+            }
+        }
+    }
+    gain_xp(PLAYER_ID, xp_to_gain, world);
+
+    UseResult::UsedUp
+}
+
+/// Finds the closest visible monster within range.
+fn closest_monster(ui: &Ui, world: &World, max_range: i32) -> Option<Entity> {
+    let mut closest_enemy = None;
+    let mut closest_dist = (max_range + 1) as f32;
+
+    for (entity, _) in world.ais.iter() {
+        let position = match world.position(entity) {
+            Some(position) => position,
+            None => continue,
+        };
+        if entity != PLAYER_ID && world.fighters.contains(entity) && ui.fov.is_in_fov(position.0, position.1) {
+            let dist = distance(world.player_position(), position);
+            if dist < closest_dist {
+                closest_enemy = Some(entity);
+                closest_dist = dist;
+            }
+        }
+    }
+    closest_enemy
+}
+
+fn toggle_equipment(inventory_id: usize, _ui: &mut Ui, game: &mut Game, world: &mut World) -> UseResult {
+    let item = game.inventory[inventory_id];
+    let equipment = match world.equipment.get(item) {
+        Some(&equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        dequip(item, game, world);
+    } else {
+        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory, world) {
+            dequip(game.inventory[current], game, world);
+        }
+        equip(item, &mut game.messages, world);
+    }
+    UseResult::UsedAndKept
+}
+
+pub fn get_equipped_in_slot(slot: Slot, inventory: &[Entity], world: &World) -> Option<usize> {
+    inventory.iter().position(|&item| {
+        world.equipment.get(item).is_some_and(|e| e.equipped && e.slot == slot)
+    })
+}
+
+pub fn equip(item: Entity, messages: &mut Messages, world: &mut World) {
+    if let Some(equipment) = world.equipment.get_mut(item) {
+        if !equipment.equipped {
+            equipment.equipped = true;
+            let slot = equipment.slot;
+            messages.add(format!("Equipped {} on {}.", world.name(item), slot), LIGHT_GREEN);
+        }
+    } else {
+        messages.add(format!("Can't equip {:?} because it's not an Equipment.", world.name(item)), RED);
+    }
+}
+
+/// Takes the item off. Hit points above the maximum without its bonus are lost.
+pub fn dequip(item: Entity, game: &mut Game, world: &mut World) {
+    if let Some(equipment) = world.equipment.get_mut(item) {
+        if equipment.equipped {
+            equipment.equipped = false;
+            let slot = equipment.slot;
+            game.messages.add(format!("Dequipped {} from {}.", world.name(item), slot), YELLOW);
+            let max_hp = max_hp(PLAYER_ID, game, world);
+            if let Some(fighter) = world.fighters.get_mut(PLAYER_ID) {
+                fighter.hp = fighter.hp.min(max_hp);
+            }
+        }
+    } else {
+        game.messages.add(format!("Can't dequip {:?} because it's not an Equipment.", world.name(item)), RED);
+    }
+}
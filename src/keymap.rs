@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use crate::backend::{Key, KeyCode};
+
+pub const KEYMAP_FILE: &str = "keys.cfg";
+
+/// Everything the player can ask for from the map screen, independent of the key that triggers it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    Move(i32, i32),
+    Wait,
+    PickUp,
+    Inventory,
+    Drop,
+    Throw,
+    Fire,
+    Descend,
+    Look,
+    CloseDoor,
+    Search,
+    Disarm,
+    MessageLog,
+    Help,
+    ToggleFullscreen,
+    Quit,
+}
+
+/// Every command with the name used in the keymap file and the description shown on the help screen.
+const COMMANDS: &[(Command, &str, &str)] = &[
+    (Command::Move(0, -1), "move_north", "Move or attack north"),
+    (Command::Move(0, 1), "move_south", "Move or attack south"),
+    (Command::Move(-1, 0), "move_west", "Move or attack west"),
+    (Command::Move(1, 0), "move_east", "Move or attack east"),
+    (Command::Move(-1, -1), "move_north_west", "Move or attack north-west"),
+    (Command::Move(1, -1), "move_north_east", "Move or attack north-east"),
+    (Command::Move(-1, 1), "move_south_west", "Move or attack south-west"),
+    (Command::Move(1, 1), "move_south_east", "Move or attack south-east"),
+    (Command::Wait, "wait", "Wait a turn"),
+    (Command::PickUp, "pick_up", "Pick up an item"),
+    (Command::Inventory, "inventory", "Use an item"),
+    (Command::Drop, "drop", "Drop an item"),
+    (Command::Throw, "throw", "Throw an item"),
+    (Command::Fire, "fire", "Shoot the held bow or sling"),
+    (Command::Descend, "descend", "Take the stairs down"),
+    (Command::Look, "look", "Look around"),
+    (Command::CloseDoor, "close_door", "Close a door"),
+    (Command::Search, "search", "Search for secret doors and traps"),
+    (Command::Disarm, "disarm", "Disarm a trap"),
+    (Command::MessageLog, "message_log", "Show the message history"),
+    (Command::Help, "help", "Show this help"),
+    (Command::ToggleFullscreen, "toggle_fullscreen", "Toggle fullscreen"),
+    (Command::Quit, "quit", "Save and quit"),
+];
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Command> {
+        COMMANDS.iter().find(|c| c.1 == name).map(|c| c.0)
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<Key, Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Command::*;
+        use KeyCode::*;
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        let defaults = [
+            (Move(0, -1), vec![Key::new(Up), Key::new(NumPad(8)), Key::char('k')]),
+            (Move(0, 1), vec![Key::new(Down), Key::new(NumPad(2)), Key::char('j')]),
+            (Move(-1, 0), vec![Key::new(Left), Key::new(NumPad(4)), Key::char('h')]),
+            (Move(1, 0), vec![Key::new(Right), Key::new(NumPad(6)), Key::char('l')]),
+            (Move(-1, -1), vec![Key::new(Home), Key::new(NumPad(7)), Key::char('y')]),
+            (Move(1, -1), vec![Key::new(PageUp), Key::new(NumPad(9)), Key::char('u')]),
+            (Move(-1, 1), vec![Key::new(End), Key::new(NumPad(1)), Key::char('b')]),
+            (Move(1, 1), vec![Key::new(PageDown), Key::new(NumPad(3)), Key::char('n')]),
+            (Wait, vec![Key::new(NumPad(5)), Key::char('.')]),
+            (PickUp, vec![Key::char('g')]),
+            (Inventory, vec![Key::char('i')]),
+            (Drop, vec![Key::char('d')]),
+            (Throw, vec![Key::char('t')]),
+            (Fire, vec![Key::char('f')]),
+            (Descend, vec![Key::char('<')]),
+            (Look, vec![Key::char('x')]),
+            (CloseDoor, vec![Key::char('c')]),
+            (Search, vec![Key::char('s')]),
+            (Disarm, vec![Key::char('D')]),
+            (MessageLog, vec![Key::char('m')]),
+            (Help, vec![Key::char('?')]),
+            (ToggleFullscreen, vec![Key { alt: true, ..Key::new(Enter) }]),
+            (Quit, vec![Key::new(Escape)]),
+        ];
+        for (command, keys) in defaults {
+            for key in keys {
+                keymap.bind(key, command);
+            }
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    pub fn command(&self, key: Key) -> Option<Command> {
+        self.bindings.get(&normalize(key)).copied()
+    }
+
+    pub fn bind(&mut self, key: Key, command: Command) {
+        self.bindings.insert(normalize(key), command);
+    }
+
+    /// The keys bound to a command, in a stable order.
+    pub fn keys_for(&self, command: Command) -> Vec<Key> {
+        let mut keys: Vec<Key> = self.bindings.iter()
+            .filter(|&(_, &c)| c == command)
+            .map(|(&key, _)| key)
+            .collect();
+        keys.sort_by_key(|key| key.to_string());
+        keys
+    }
+
+    /// One line per command, listing its keys.
+    pub fn help_text(&self) -> String {
+        COMMANDS.iter()
+            .map(|&(command, _, description)| {
+                let keys = self.keys_for(command).iter().map(Key::to_string).collect::<Vec<_>>();
+                format!("{}: {}", description, keys.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Reads `key = command` lines on top of the default bindings. A missing file leaves the defaults alone.
+    pub fn load(path: &str) -> Result<Keymap, KeymapError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Keymap::parse(path, &contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Keymap::default()),
+            Err(e) => Err(KeymapError { path: path.into(), line: 0, message: e.to_string() }),
+        }
+    }
+
+    /// The bindings in the text of a keymap file, `path` is only used in the errors.
+    fn parse(path: &str, contents: &str) -> Result<Keymap, KeymapError> {
+        let mut keymap = Keymap::default();
+        let mut user_bindings: HashMap<Key, (Command, usize)> = HashMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: String| KeymapError { path: path.into(), line: line_number, message };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, command) = line.rsplit_once('=')
+                .ok_or_else(|| error(format!("expected `key = command`, found `{}`", line)))?;
+            let key = normalize(parse_key(key.trim()).map_err(error)?);
+            let command = Command::from_name(command.trim())
+                .ok_or_else(|| error(format!("unknown command `{}`", command.trim())))?;
+
+            if let Some(&(bound_command, bound_line)) = user_bindings.get(&key) {
+                if bound_command != command {
+                    return Err(error(format!("`{}` is already bound to a different command on line {}", key, bound_line)));
+                }
+            }
+            user_bindings.insert(key, (command, line_number));
+            keymap.bind(key, command);
+        }
+        Ok(keymap)
+    }
+}
+
+#[derive(Debug)]
+pub struct KeymapError {
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "{}:{}: {}", self.path, self.line, self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl Error for KeymapError {}
+
+/// A typed character already includes the shift key, so it is ignored for those.
+fn normalize(mut key: Key) -> Key {
+    if let KeyCode::Char(_) = key.code {
+        key.shift = false;
+    }
+    key
+}
+
+/// Parses keys such as `k`, `Up`, `NumPad8` or `Ctrl+s`.
+fn parse_key(text: &str) -> Result<Key, String> {
+    let mut key = Key::default();
+    let mut rest = text;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        if lower.starts_with("ctrl+") && rest.len() > 5 {
+            key.ctrl = true;
+            rest = &rest[5..];
+        } else if lower.starts_with("alt+") && rest.len() > 4 {
+            key.alt = true;
+            rest = &rest[4..];
+        } else if lower.starts_with("shift+") && rest.len() > 6 {
+            key.shift = true;
+            rest = &rest[6..];
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = rest.chars();
+    key.code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => {
+            let lower = rest.to_ascii_lowercase();
+            match lower.as_str() {
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "enter" => KeyCode::Enter,
+                "escape" => KeyCode::Escape,
+                "backspace" => KeyCode::Backspace,
+                "space" => KeyCode::Spacebar,
+                _ => match lower.strip_prefix("numpad").and_then(|digit| digit.parse().ok()) {
+                    Some(digit) if digit <= 9 => KeyCode::NumPad(digit),
+                    _ => return Err(format!("unknown key `{}`", text)),
+                },
+            }
+        }
+    };
+    Ok(key)
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::None => write!(f, "None"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::NumPad(digit) => write!(f, "NumPad{}", digit),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Escape => write!(f, "Escape"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Spacebar => write!(f, "Space"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Keymap, KeymapError> {
+        Keymap::parse("keys.cfg", contents)
+    }
+
+    #[test]
+    fn overrides_keep_the_other_defaults() {
+        let keymap = parse("# vi keys are hard\nw = move_north\nCtrl+s = quit\n").unwrap();
+        assert_eq!(keymap.command(Key::char('w')), Some(Command::Move(0, -1)));
+        assert_eq!(keymap.command(Key { ctrl: true, ..Key::char('s') }), Some(Command::Quit));
+        assert_eq!(keymap.command(Key::char('k')), Some(Command::Move(0, -1)));
+        assert_eq!(keymap.command(Key::new(KeyCode::Escape)), Some(Command::Quit));
+    }
+
+    #[test]
+    fn rejects_an_unknown_key() {
+        let error = parse(". = wait\nSuper+x = quit\n").err().unwrap();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "unknown key `Super+x`");
+    }
+
+    #[test]
+    fn rejects_an_unknown_command() {
+        let error = parse("x = explode").err().unwrap();
+        assert_eq!(error.line, 1);
+        assert_eq!(error.message, "unknown command `explode`");
+    }
+
+    #[test]
+    fn rejects_a_line_without_equals() {
+        let error = parse("\nx look\n").err().unwrap();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "expected `key = command`, found `x look`");
+    }
+
+    #[test]
+    fn rejects_a_key_bound_to_two_commands() {
+        let error = parse("x = look\nx = wait\n").err().unwrap();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "`x` is already bound to a different command on line 1");
+        assert!(parse("x = look\nx = look\n").is_ok());
+    }
+}
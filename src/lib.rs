@@ -1,34 +1,49 @@
-use std::cmp;
-use crate::gamemap::GameMap;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use crate::gamemap::{GameMap, MapGenerator};
 use crate::gui::Messages;
-use crate::object::Object;
+use crate::ecs::Entity;
 
-pub mod object;
 pub mod gamemap;
 pub mod ai;
 pub mod gui;
 pub mod inventory;
 pub mod menu;
+pub mod save;
+pub mod backend;
+pub mod tcod_backend;
+pub mod headless;
+pub mod engine;
+pub mod targeting;
+pub mod pathfinding;
+pub mod keymap;
+pub mod message_log;
+pub mod ecs;
+pub mod components;
+pub mod combat;
+pub mod movement;
+pub mod scheduler;
+pub mod content;
+pub mod vault;
+pub mod terrain;
+pub mod traps;
+pub mod effects;
+pub mod ranged;
 
 pub const SCREEN_WIDTH: i32 = 80;
 pub const SCREEN_HEIGHT: i32 = 50;
 
-pub const PLAYER_ID: usize = 0;
+/// The player is the first object of every game and is never removed.
+pub const PLAYER_ID: Entity = Entity::FIRST;
 
+#[derive(Serialize, Deserialize)]
 pub struct Game {
     pub map: GameMap,
     pub messages: Messages,
-    pub inventory: Vec<Object>,
+    /// Carried items, they have no position on the map.
+    pub inventory: Vec<Entity>,
+    pub dungeon_level: u32,
+    pub map_generator: MapGenerator,
+    pub seed: u64,
+    pub rng: Pcg64,
 }
-
-pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
-    assert_ne!(first_index, second_index);
-
-    let split_at_index = cmp::max(first_index, second_index);
-    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
-    if first_index < second_index {
-        (&mut first_slice[first_index], &mut second_slice[0])
-    } else {
-        (&mut second_slice[0], &mut first_slice[second_index])
-    }
-}
\ No newline at end of file
@@ -1,59 +1,69 @@
-use tcod::colors::WHITE;
-use tcod::{BackgroundFlag, Console, TextAlignment};
-use tcod::console::{blit, Offscreen, Root};
-use crate::{Object, SCREEN_HEIGHT, SCREEN_WIDTH};
+use tcod::colors::{BLACK, WHITE};
+use tcod::TextAlignment;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::backend::{Input, KeyCode, Renderer, text_height};
+use crate::ecs::{Entity, World};
+use crate::inventory::display_name;
 
 const INVENTORY_WIDTH: i32 = 50;
 
-pub fn inventory_menu(inventory: &[Object], header: &str, root: &mut Root) -> Option<usize> {
-    let options = if inventory.len() == 0 {
+pub fn inventory_menu(inventory: &[Entity], world: &World, header: &str, renderer: &mut dyn Renderer, input: &mut dyn Input) -> Option<usize> {
+    let options = if inventory.is_empty() {
         vec!["Inventory is empty".into()]
     } else {
-        inventory.iter().map(|item| item.name.clone()).collect()
+        inventory.iter().map(|&item| {
+            match world.equipment.get(item) {
+                Some(equipment) if equipment.equipped => format!("{} (on {})", world.name(item), equipment.slot),
+                _ => display_name(item, world),
+            }
+        }).collect()
     };
 
-    let inventory_index = menu(header, &options, INVENTORY_WIDTH, root);
+    let inventory_index = menu(header, &options, INVENTORY_WIDTH, renderer, input);
 
-    if inventory.len() > 0 {
+    if !inventory.is_empty() {
         inventory_index
     } else {
         None
     }
 }
 
-pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root) -> Option<usize> {
+pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, renderer: &mut dyn Renderer, input: &mut dyn Input) -> Option<usize> {
     assert!(options.len() <= 26, "Cannot have a menu with more than 26 options!");
 
-    let header_height = root.get_height_rect(0, 0, width, SCREEN_HEIGHT, header);
+    let header_height = text_height(header, width);
     let height = options.len() as i32 + header_height;
 
-    let mut window = Offscreen::new(width, height);
+    let x = SCREEN_WIDTH / 2 - width / 2;
+    let y = SCREEN_HEIGHT / 2 - height / 2;
 
-    window.set_default_foreground(WHITE);
-    window.print_rect_ex(0, 0, width, height, BackgroundFlag::None, TextAlignment::Left, header);
+    renderer.fill_background(x, y, width, height, BLACK);
+    renderer.print_rect(x, y, width, header, WHITE);
 
     for (i, option_text) in options.iter().enumerate() {
         let menu_letter = (b'a' + i as u8) as char;
         let text = format!("({}) {}", menu_letter, option_text.as_ref());
-        window.print_ex(0, header_height + i as i32, BackgroundFlag::None, TextAlignment::Left, text);
+        renderer.print(x, y + header_height + i as i32, &text, WHITE, TextAlignment::Left);
     }
 
-    let x = SCREEN_WIDTH / 2 - width / 2;
-    let y = SCREEN_HEIGHT / 2 - height / 2;
-    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
-
-    root.flush();
+    renderer.flush();
 
-    let key = root.wait_for_keypress(true);
+    let key = input.wait_for_keypress();
 
-    if key.printable.is_alphabetic() {
-        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
-        if index < options.len() {
-            Some(index)
-        } else {
-            None
+    match key.code {
+        KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+            let index = c.to_ascii_lowercase() as usize - 'a' as usize;
+            if index < options.len() {
+                Some(index)
+            } else {
+                None
+            }
         }
-    } else {
-        None
+        _ => None,
     }
-}
\ No newline at end of file
+}
+
+pub fn msgbox(text: &str, width: i32, renderer: &mut dyn Renderer, input: &mut dyn Input) {
+    let options: &[&str] = &[];
+    menu(text, options, width, renderer, input);
+}
@@ -0,0 +1,149 @@
+use tcod::Color;
+use tcod::colors::{DARKER_BLUE, LIGHT_GREY, LIGHT_YELLOW, WHITE};
+use tcod::TextAlignment;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::backend::{Input, KeyCode, Renderer, wrap_text};
+use crate::gui::Messages;
+
+const LOG_X: i32 = 1;
+const LOG_WIDTH: i32 = SCREEN_WIDTH - 2;
+/// Rows left for messages between the title and the status line.
+const LOG_HEIGHT: usize = SCREEN_HEIGHT as usize - 2;
+
+const MATCH_BACKGROUND: Color = DARKER_BLUE;
+
+struct LogLine {
+    text: String,
+    color: Color,
+    /// Index of the message the line belongs to, so a match highlights every line of it.
+    message: usize,
+}
+
+/// Full-screen, scrollable view of every retained message. Up/Down scroll a line, PageUp/PageDown a page,
+/// Home/End jump to the oldest/newest message, `/` searches, `n`/`N` go to the previous/next match.
+pub fn show_message_log(messages: &Messages, renderer: &mut dyn Renderer, input: &mut dyn Input) {
+    let lines = wrap_messages(messages);
+    let max_top = lines.len().saturating_sub(LOG_HEIGHT);
+    let mut top = max_top;
+    let mut search = String::new();
+    let mut status = String::new();
+
+    loop {
+        draw_log(renderer, messages, &lines, top, &search, &status);
+        renderer.flush();
+        status.clear();
+
+        let key = input.wait_for_keypress();
+        match key.code {
+            KeyCode::Escape | KeyCode::Char('q') => return,
+            KeyCode::Up | KeyCode::NumPad(8) | KeyCode::Char('k') => top = top.saturating_sub(1),
+            KeyCode::Down | KeyCode::NumPad(2) | KeyCode::Char('j') => top = (top + 1).min(max_top),
+            KeyCode::PageUp => top = top.saturating_sub(LOG_HEIGHT),
+            KeyCode::PageDown => top = (top + LOG_HEIGHT).min(max_top),
+            KeyCode::Home => top = 0,
+            KeyCode::End => top = max_top,
+            KeyCode::Char('/') => {
+                if let Some(text) = read_search(renderer, input) {
+                    search = text;
+                    // search backwards from the bottom of the screen, the newest messages are the likeliest
+                    let from = (top + LOG_HEIGHT).min(lines.len());
+                    match find_match(&lines, messages, &search, from, true) {
+                        Some(line) => top = line.min(max_top),
+                        None => status = format!("No match for \"{}\".", search),
+                    }
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') if !search.is_empty() => {
+                let backwards = key.code == KeyCode::Char('n');
+                let from = if backwards {
+                    top
+                } else {
+                    // skip the rest of the message at the top of the screen
+                    let current = lines.get(top).map_or(0, |l| l.message);
+                    lines.iter().position(|l| l.message > current).unwrap_or(lines.len())
+                };
+                match find_match(&lines, messages, &search, from, backwards) {
+                    Some(line) => top = line.min(max_top),
+                    None => status = format!("No more matches for \"{}\".", search),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn wrap_messages(messages: &Messages) -> Vec<LogLine> {
+    messages.iter()
+        .enumerate()
+        .flat_map(|(index, message)| {
+            wrap_text(&message.display_text(), LOG_WIDTH)
+                .into_iter()
+                .map(move |text| LogLine { text, color: message.color, message: index })
+        })
+        .collect()
+}
+
+fn is_match(messages: &Messages, message: usize, search: &str) -> bool {
+    !search.is_empty() && messages.iter()
+        .nth(message)
+        .is_some_and(|m| m.text.to_lowercase().contains(&search.to_lowercase()))
+}
+
+/// First line of the nearest matching message before (`backwards`) or at/after `from`.
+fn find_match(lines: &[LogLine], messages: &Messages, search: &str, from: usize, backwards: bool) -> Option<usize> {
+    let matching = |index: &usize| is_match(messages, lines[*index].message, search);
+    let line = if backwards {
+        (0..from.min(lines.len())).rev().find(matching)
+    } else {
+        (from..lines.len()).find(matching)
+    }?;
+
+    let message = lines[line].message;
+    lines.iter().position(|l| l.message == message)
+}
+
+fn draw_log(renderer: &mut dyn Renderer, messages: &Messages, lines: &[LogLine], top: usize, search: &str, status: &str) {
+    renderer.clear();
+
+    let title = format!("Message log ({} messages)", messages.len());
+    renderer.print(LOG_X, 0, &title, LIGHT_YELLOW, TextAlignment::Left);
+
+    for (row, line) in lines.iter().skip(top).take(LOG_HEIGHT).enumerate() {
+        let y = row as i32 + 1;
+        if is_match(messages, line.message, search) {
+            renderer.fill_background(LOG_X, y, LOG_WIDTH, 1, MATCH_BACKGROUND);
+        }
+        renderer.print(LOG_X, y, &line.text, line.color, TextAlignment::Left);
+    }
+
+    let status = if !status.is_empty() {
+        status.to_string()
+    } else if !search.is_empty() {
+        format!("Searching for \"{}\", n/N for older/newer matches, Escape to close.", search)
+    } else {
+        "Up/Down, PageUp/PageDown and Home/End scroll, / searches, Escape closes.".to_string()
+    };
+    renderer.print(LOG_X, SCREEN_HEIGHT - 1, &status, LIGHT_GREY, TextAlignment::Left);
+}
+
+/// Reads the search text on the status line, `None` when cancelled with Escape.
+fn read_search(renderer: &mut dyn Renderer, input: &mut dyn Input) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        let prompt = format!("Search: {}_", text);
+        renderer.print(LOG_X, SCREEN_HEIGHT - 1, &format!("{:1$}", prompt, LOG_WIDTH as usize), WHITE, TextAlignment::Left);
+        renderer.flush();
+
+        let key = input.wait_for_keypress();
+        match key.code {
+            KeyCode::Enter => return Some(text),
+            KeyCode::Escape => return None,
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Spacebar => text.push(' '),
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use rand::Rng;
+use crate::{Game, PLAYER_ID};
+use crate::combat::attack;
+use crate::ecs::{Entity, World};
+use crate::effects::{has_effect, EffectKind};
+use crate::gamemap::{is_blocked, TileKind};
+use crate::scheduler::Action;
+use crate::terrain::enter_terrain;
+use crate::traps::trigger_traps;
+
+pub fn distance((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> f32 {
+    (((x2 - x1).pow(2) + (y2 - y1).pow(2)) as f32).sqrt()
+}
+
+/// Moves the entity unless something is in the way, returning the action so climbing rubble costs more. Bumping
+/// a closed door opens it. A confused entity goes a random way instead.
+pub fn move_by(entity: Entity, dx: i32, dy: i32, game: &mut Game, world: &mut World) -> Action {
+    let (dx, dy) = confused_direction(entity, (dx, dy), game, world);
+    step(entity, dx, dy, game, world)
+}
+
+/// Moves in a random direction.
+pub fn stumble(entity: Entity, game: &mut Game, world: &mut World) -> Action {
+    let dx = game.rng.gen_range(-1..=1);
+    let dy = game.rng.gen_range(-1..=1);
+    step(entity, dx, dy, game, world)
+}
+
+fn confused_direction(entity: Entity, direction: (i32, i32), game: &mut Game, world: &World) -> (i32, i32) {
+    if has_effect(entity, EffectKind::Confused, world) {
+        (game.rng.gen_range(-1..=1), game.rng.gen_range(-1..=1))
+    } else {
+        direction
+    }
+}
+
+fn step(entity: Entity, dx: i32, dy: i32, game: &mut Game, world: &mut World) -> Action {
+    let (x, y) = match world.position(entity) {
+        Some(position) => position,
+        None => return Action::Move,
+    };
+    let (x, y) = (x + dx, y + dy);
+    if game.map[x as usize][y as usize].kind == TileKind::ClosedDoor {
+        game.map[x as usize][y as usize].kind = TileKind::OpenDoor;
+        return Action::OpenDoor;
+    }
+    if is_blocked(x, y, entity, &game.map, world) {
+        return Action::Move;
+    }
+    world.set_position(entity, x, y);
+    enter_terrain(entity, game, world);
+    if world.is_alive(entity) {
+        trigger_traps(entity, game, world);
+    }
+    if game.map[x as usize][y as usize].kind == TileKind::Rubble {
+        Action::Climb
+    } else {
+        Action::Move
+    }
+}
+
+pub fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, world: &mut World) -> Action {
+    let (dx, dy) = confused_direction(PLAYER_ID, (dx, dy), game, world);
+    let (px, py) = world.player_position();
+    let (x, y) = (px + dx, py + dy);
+
+    // a confused player may stumble in place and should not hit themself
+    let target = world.entities_at(x, y).find(|&e| e != PLAYER_ID && world.fighters.contains(e));
+
+    match target {
+        Some(target) => {
+            attack(PLAYER_ID, target, game, world);
+            Action::Attack
+        }
+        None => step(PLAYER_ID, dx, dy, game, world),
+    }
+}
@@ -0,0 +1,175 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::gamemap::{GameMap, TileKind, MAP_HEIGHT, MAP_WIDTH};
+use crate::ecs::{Entity, World};
+use crate::scheduler::Action;
+use crate::terrain::can_enter;
+use crate::traps::known_trap_at;
+
+/// Nodes expanded before the search gives up, keeps a floor full of monsters fast.
+const MAX_SEARCH_NODES: usize = 500;
+
+/// Extra cost of stepping through a tile another creature stands on. Creatures move, so they are only detoured
+/// around when there is a reasonably short way past them.
+const BLOCKING_OBJECT_COST: i32 = 10;
+
+/// Extra cost of a closed door on the way, opening it takes a turn before stepping through.
+const CLOSED_DOOR_COST: i32 = 1;
+
+/// Diagonal steps cost the same as straight ones, just like for the player.
+const DIRECTIONS: [(i32, i32); 8] = [(0, -1), (0, 1), (-1, 0), (1, 0), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// A* search over the map for `mover`, who only crosses the terrain it can enter and the closed doors it can
+/// open. Returns the steps from `from` (exclusive) to `to` (inclusive), or `None` if there is no path or the
+/// search hit its limit.
+pub fn find_path(from: (i32, i32), to: (i32, i32), mover: Entity, map: &GameMap, world: &World) -> Option<Vec<(i32, i32)>> {
+    let blocking: HashSet<(i32, i32)> = world.blockers.iter()
+        .filter_map(|(entity, _)| world.position(entity))
+        .collect();
+
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::new();
+    let mut cost_so_far = HashMap::new();
+    open.push(Reverse((distance(from, to), 0, from)));
+    cost_so_far.insert(from, 0);
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, cost, current))) = open.pop() {
+        if current == to {
+            return Some(reconstruct_path(&came_from, from, to));
+        }
+        // a cheaper way here was found after this entry was queued
+        if cost > cost_so_far[&current] {
+            continue;
+        }
+
+        expanded += 1;
+        if expanded > MAX_SEARCH_NODES {
+            return None;
+        }
+
+        for (dx, dy) in DIRECTIONS {
+            let next = (current.0 + dx, current.1 + dy);
+            if !in_map(next) {
+                continue;
+            }
+            let tile = map[next.0 as usize][next.1 as usize];
+            let closed_door = tile.kind == TileKind::ClosedDoor;
+            if !closed_door && (tile.blocked() || !can_enter(mover, tile.kind, world)) {
+                continue;
+            }
+            // monsters keep off the traps they know about
+            if next != to && known_trap_at(next.0, next.1, world).is_some() {
+                continue;
+            }
+
+            // rubble takes as many turns to cross as the climb costs
+            let terrain_cost = match tile.kind {
+                TileKind::Rubble => Action::Climb.cost() / Action::Move.cost(),
+                TileKind::ClosedDoor => 1 + CLOSED_DOOR_COST,
+                _ => 1,
+            };
+            let step_cost = if next != to && blocking.contains(&next) { terrain_cost + BLOCKING_OBJECT_COST } else { terrain_cost };
+            let new_cost = cost + step_cost;
+            if cost_so_far.get(&next).is_none_or(|&old_cost| new_cost < old_cost) {
+                cost_so_far.insert(next, new_cost);
+                came_from.insert(next, current);
+                open.push(Reverse((new_cost + distance(next, to), new_cost, next)));
+            }
+        }
+    }
+    None
+}
+
+fn reconstruct_path(came_from: &HashMap<(i32, i32), (i32, i32)>, from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut path = vec![];
+    let mut current = to;
+    while current != from {
+        path.push(current);
+        current = came_from[&current];
+    }
+    path.reverse();
+    path
+}
+
+fn distance((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> i32 {
+    (x1 - x2).abs().max((y1 - y2).abs())
+}
+
+fn in_map((x, y): (i32, i32)) -> bool {
+    (0..MAP_WIDTH).contains(&x) && (0..MAP_HEIGHT).contains(&y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{BlocksMovement, Position};
+    use crate::gamemap::Tile;
+
+    /// A level of solid rock with a floor rectangle dug out of it.
+    fn dug_out(x1: i32, y1: i32, x2: i32, y2: i32) -> GameMap {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for x in x1..=x2 {
+            for y in y1..=y2 {
+                map[x as usize][y as usize] = Tile::empty();
+            }
+        }
+        map
+    }
+
+    fn assert_walkable(path: &[(i32, i32)], from: (i32, i32), to: (i32, i32), map: &GameMap) {
+        assert_eq!(path.last(), Some(&to));
+        let mut previous = from;
+        for &step in path {
+            assert_eq!(distance(previous, step), 1);
+            assert!(!map[step.0 as usize][step.1 as usize].blocked());
+            previous = step;
+        }
+    }
+
+    #[test]
+    fn goes_around_a_wall_corner() {
+        let mut map = dug_out(1, 1, 10, 10);
+        for tile in &mut map[5][1..=7] {
+            *tile = Tile::wall();
+        }
+        let mut world = World::new();
+        let monster = world.spawn().with(Position::new(4, 2)).build();
+
+        let path = find_path((4, 2), (6, 2), monster, &map, &world).unwrap();
+        assert_walkable(&path, (4, 2), (6, 2), &map);
+        assert!(path.contains(&(5, 8)));
+        assert_eq!(path.len(), 12);
+    }
+
+    #[test]
+    fn creatures_in_the_way_cost_more_but_do_not_block() {
+        let mut world = World::new();
+        let monster = world.spawn().with(Position::new(1, 5)).build();
+        world.spawn().with(Position::new(5, 5)).with(BlocksMovement).build();
+
+        let corridor = dug_out(1, 5, 10, 5);
+        let path = find_path((1, 5), (10, 5), monster, &corridor, &world).unwrap();
+        assert_walkable(&path, (1, 5), (10, 5), &corridor);
+        assert!(path.contains(&(5, 5)));
+
+        let wide_corridor = dug_out(1, 5, 10, 6);
+        let path = find_path((1, 5), (10, 5), monster, &wide_corridor, &world).unwrap();
+        assert_walkable(&path, (1, 5), (10, 5), &wide_corridor);
+        assert!(!path.contains(&(5, 5)));
+    }
+
+    #[test]
+    fn gives_up_on_long_detours() {
+        let mut map = dug_out(1, 1, MAP_WIDTH - 2, MAP_HEIGHT - 2);
+        for y in 1..MAP_HEIGHT - 2 {
+            map[40][y as usize] = Tile::wall();
+        }
+        let mut world = World::new();
+        let monster = world.spawn().with(Position::new(30, 1)).build();
+
+        // the only way is down the whole wall and back up
+        assert_eq!(find_path((30, 1), (50, 1), monster, &map, &world), None);
+        assert!(find_path((39, MAP_HEIGHT - 3), (41, MAP_HEIGHT - 3), monster, &map, &world).is_some());
+    }
+}
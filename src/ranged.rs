@@ -0,0 +1,147 @@
+//! Missiles: the player's bows and slings, and monsters that shoot from afar.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::{LIGHT_CYAN, RED, WHITE};
+use crate::{Game, PLAYER_ID};
+use crate::combat::{defense, gain_xp, take_damage};
+use crate::components::Slot;
+use crate::ecs::{Entity, World};
+use crate::engine::Ui;
+use crate::gamemap::GameMap;
+use crate::inventory::{get_equipped_in_slot, take_one};
+use crate::targeting::target_line;
+
+/// Percentage chance that a fired arrow or stone breaks instead of landing on the floor.
+const AMMO_BREAK_CHANCE: u32 = 25;
+
+/// A weapon that shoots the ammunition with the name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Launcher {
+    pub ammo: String,
+    pub range: i32,
+    /// Damage of a shot before the target's defense.
+    pub power: i32,
+}
+
+/// A monster's attack from a distance.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RangedAttack {
+    /// What it shoots, for the messages.
+    pub projectile: String,
+    pub range: i32,
+    pub power: i32,
+}
+
+/// Follows a straight line from `from` towards `to`. Returns the tiles the missile crosses, up to the wall in the
+/// way or the first creature standing in it, and that creature.
+pub fn fly(from: (i32, i32), to: (i32, i32), map: &GameMap, world: &World) -> (Vec<(i32, i32)>, Option<Entity>) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs());
+    let mut path = vec![];
+    for step in 1..=steps {
+        let x = from.0 + (dx as f32 * step as f32 / steps as f32).round() as i32;
+        let y = from.1 + (dy as f32 * step as f32 / steps as f32).round() as i32;
+        if map[x as usize][y as usize].blocked() {
+            break;
+        }
+        path.push((x, y));
+        let hit = world.entities_at(x, y).find(|&e| world.blockers.contains(e));
+        if hit.is_some() {
+            return (path, hit);
+        }
+    }
+    (path, None)
+}
+
+/// Whether a missile from `from` would reach the target before anything else.
+pub fn clear_shot(from: (i32, i32), target: Entity, map: &GameMap, world: &World) -> bool {
+    match world.position(target) {
+        Some(to) => fly(from, to, map, world).1 == Some(target),
+        None => false,
+    }
+}
+
+/// The launcher the player holds, if any.
+pub fn equipped_launcher(game: &Game, world: &World) -> Option<Launcher> {
+    let weapon = get_equipped_in_slot(Slot::MainHand, &game.inventory, world)?;
+    world.get::<Launcher>(game.inventory[weapon]).cloned()
+}
+
+/// The carried ammunition the held launcher shoots.
+pub fn find_ammo(game: &Game, world: &World) -> Option<usize> {
+    let launcher = equipped_launcher(game, world)?;
+    game.inventory.iter().position(|&item| world.name(item) == launcher.ammo)
+}
+
+/// Shoots one of the ammunition stack at a tile picked along a targeting line. The missile lands where it stops
+/// unless it breaks. Returns whether the player shot.
+pub fn fire(ammo_index: usize, ui: &mut Ui, game: &mut Game, world: &mut World) -> bool {
+    let ammo_name = world.name(game.inventory[ammo_index]).to_string();
+    let launcher = match equipped_launcher(game, world) {
+        Some(launcher) if launcher.ammo == ammo_name => launcher,
+        Some(launcher) => {
+            game.messages.add(format!("You cannot shoot {} with a weapon made for {}.", ammo_name, launcher.ammo), RED);
+            return false;
+        }
+        None => {
+            game.messages.add(format!("You hold nothing to shoot the {} with.", ammo_name), RED);
+            return false;
+        }
+    };
+    game.messages.add("Shoot where? Pick a tile with the mouse or the arrow keys and Enter, or Escape to cancel.", LIGHT_CYAN);
+    let target = match target_line(ui, game, world, Some(launcher.range as f32)) {
+        Some(tile_pos) => tile_pos,
+        None => return false,
+    };
+
+    let ammo = take_one(ammo_index, game, world);
+    let from = world.player_position();
+    let (path, hit) = fly(from, target, &game.map, world);
+    match hit {
+        Some(target) => {
+            let damage = launcher.power - defense(target, game, world);
+            if damage > 0 {
+                game.messages.add(format!("The {} hits the {} for {} hit points.", ammo_name, world.name(target), damage), WHITE);
+                if let Some(xp) = take_damage(target, damage, game, world) {
+                    gain_xp(PLAYER_ID, xp, world);
+                }
+            } else {
+                game.messages.add(format!("The {} glances off the {}.", ammo_name, world.name(target)), WHITE);
+            }
+        }
+        None => game.messages.add(format!("The {} hits nothing.", ammo_name), WHITE),
+    }
+
+    if game.rng.gen_range(0..100) < AMMO_BREAK_CHANCE {
+        world.despawn(ammo);
+    } else {
+        let (x, y) = path.last().copied().unwrap_or(from);
+        world.set_position(ammo, x, y);
+    }
+    true
+}
+
+/// A monster shoots its ranged attack at a tile, hitting whatever is in the way first.
+pub fn shoot(shooter: Entity, target: (i32, i32), game: &mut Game, world: &mut World) {
+    let (attack, from) = match (world.get::<RangedAttack>(shooter).cloned(), world.position(shooter)) {
+        (Some(attack), Some(from)) => (attack, from),
+        _ => return,
+    };
+    let (_, hit) = fly(from, target, &game.map, world);
+    let shooter_name = world.name(shooter).to_string();
+    match hit {
+        Some(hit) => {
+            let damage = attack.power - defense(hit, game, world);
+            if damage > 0 {
+                game.messages.add(format!("{}'s {} hits {} for {} hit points.", shooter_name, attack.projectile, world.name(hit), damage), WHITE);
+                take_damage(hit, damage, game, world);
+            } else {
+                game.messages.add(format!("{}'s {} has no effect on {}.", shooter_name, attack.projectile, world.name(hit)), WHITE);
+            }
+        }
+        None => game.messages.add(format!("{}'s {} misses.", shooter_name, attack.projectile), WHITE),
+    }
+}
@@ -0,0 +1,55 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use serde::{Deserialize, Serialize};
+use crate::Game;
+use crate::ecs::World;
+
+/// The save file used unless the `Ui` is given another one.
+pub const SAVE_FILE: &str = "savegame";
+
+/// Bumped whenever the layout of `Game` or of a component changes, so old saves are rejected instead of misread.
+const SAVE_VERSION: u64 = 21;
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u64,
+    game: &'a Game,
+    world: &'a World,
+}
+
+/// Read first, so the rest of an old save is never parsed.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+    game: Game,
+    world: World,
+}
+
+pub fn save_game(path: &str, game: &Game, world: &World) -> Result<(), Box<dyn Error>> {
+    let save_data = serde_json::to_string(&SaveFileRef { version: SAVE_VERSION, game, world })?;
+    let mut file = File::create(path)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_game(path: &str) -> Result<(Game, World), Box<dyn Error>> {
+    let mut json_save_state = String::new();
+    let mut file = File::open(path)?;
+    file.read_to_string(&mut json_save_state)?;
+
+    let header: SaveHeader = serde_json::from_str(&json_save_state)?;
+    match header.version {
+        Some(SAVE_VERSION) => {}
+        Some(version) => return Err(format!("Save file version {} is not supported (expected {}).", version, SAVE_VERSION).into()),
+        None => return Err("Save file has no version.".into()),
+    }
+
+    // straight from the text, a `Value` would round the 128-bit RNG state to a float
+    let save_file: SaveFile = serde_json::from_str(&json_save_state)?;
+    Ok((save_file.game, save_file.world))
+}
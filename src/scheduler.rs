@@ -0,0 +1,211 @@
+//! Energy based turns.
+//!
+//! Every actor gains its speed in energy each tick and may act once it has `ACTION_ENERGY` of it; acting spends
+//! what the action costs. With normal speed and normal actions everybody acts once per tick, a creature twice
+//! as fast acts twice as often and an expensive action delays the actor's next turn.
+
+use serde::{Deserialize, Serialize};
+use tcod::Map;
+use crate::{Game, PLAYER_ID};
+use crate::ai::ai_take_turn;
+use crate::ecs::{Entity, World};
+use crate::effects::{has_effect, tick_effects, EffectKind};
+
+/// Energy needed to act.
+pub const ACTION_ENERGY: i32 = 100;
+pub const NORMAL_SPEED: i32 = 100;
+
+/// Ticks after which the scheduler gives up waiting for a player too slow to ever act.
+const MAX_TICKS: i32 = 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    /// Energy gained per tick.
+    pub speed: i32,
+    pub energy: i32,
+}
+
+impl Actor {
+    pub fn new(speed: i32) -> Self {
+        Self { speed, energy: 0 }
+    }
+
+    /// An actor that may act right away.
+    pub fn ready(speed: i32) -> Self {
+        Self { speed, energy: ACTION_ENERGY }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Move,
+    /// Moving onto rubble.
+    Climb,
+    Attack,
+    Wait,
+    UseItem,
+    Throw,
+    OpenDoor,
+    CloseDoor,
+    Search,
+    Disarm,
+    Fire,
+}
+
+impl Action {
+    pub fn cost(self) -> i32 {
+        match self {
+            Action::Move => 100,
+            Action::Climb => 200,
+            Action::Attack => 100,
+            Action::Wait => 100,
+            // a quick gulp or a glance at a scroll
+            Action::UseItem => 50,
+            Action::Throw => 100,
+            Action::OpenDoor => 100,
+            Action::CloseDoor => 100,
+            Action::Search => 100,
+            Action::Disarm => 100,
+            Action::Fire => 100,
+        }
+    }
+}
+
+pub fn spend_energy(entity: Entity, action: Action, world: &mut World) {
+    if let Some(actor) = world.actors.get_mut(entity) {
+        actor.energy -= action.cost();
+    }
+}
+
+/// Runs the other actors' turns until the player has the energy to act again, or is dead.
+pub fn run_until_player_ready(fov_map: &Map, game: &mut Game, world: &mut World) {
+    for _ in 0..MAX_TICKS {
+        if !world.is_alive(PLAYER_ID) || world.actors.get(PLAYER_ID).is_none_or(|a| a.energy >= ACTION_ENERGY) {
+            return;
+        }
+        tick(fov_map, game, world);
+    }
+}
+
+/// Energy the actor gains per tick, haste doubles it.
+pub fn speed(entity: Entity, world: &World) -> i32 {
+    let speed = world.actors.get(entity).map_or(0, |a| a.speed);
+    if has_effect(entity, EffectKind::Hasted, world) { speed * 2 } else { speed }
+}
+
+/// Wears the status effects down by a turn, gives every actor its energy, then lets each monster with enough
+/// of it act.
+fn tick(fov_map: &Map, game: &mut Game, world: &mut World) {
+    tick_effects(fov_map, game, world);
+
+    let actors: Vec<Entity> = world.actors.iter().map(|(entity, _)| entity).collect();
+    for entity in actors {
+        let speed = speed(entity, world);
+        if let Some(actor) = world.actors.get_mut(entity) {
+            actor.energy += speed;
+        }
+    }
+
+    let monsters: Vec<Entity> = world.actors.iter()
+        .map(|(entity, _)| entity)
+        .filter(|&entity| entity != PLAYER_ID)
+        .collect();
+    for monster in monsters {
+        while world.actors.get(monster).is_some_and(|a| a.energy >= ACTION_ENERGY) && world.ais.contains(monster) {
+            let action = ai_take_turn(monster, fov_map, game, world);
+            spend_energy(monster, action, world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+    use tcod::map::FovAlgorithm;
+    use super::*;
+    use crate::ai::Ai;
+    use crate::components::{BlocksMovement, DeathCallback, Fighter, Name, Position};
+    use crate::gamemap::{MapGenerator, Tile, MAP_HEIGHT, MAP_WIDTH};
+    use crate::gui::Messages;
+
+    const PLAYER_HP: i32 = 1000;
+
+    /// The player and a monster next to it on an open level, the monster hits for 1 each turn.
+    fn setup(monster_speed: i32) -> (Map, Game, World) {
+        let mut world = World::new();
+        world.spawn()
+            .with(Position::new(10, 10))
+            .with(Name::new("player"))
+            .with(BlocksMovement)
+            .with(Fighter { max_hp: PLAYER_HP, hp: PLAYER_HP, defense: 0, power: 0, xp: 0, on_death: DeathCallback::Player })
+            .with(Actor::ready(NORMAL_SPEED))
+            .build();
+        world.spawn()
+            .with(Position::new(11, 10))
+            .with(Name::new("ork"))
+            .with(BlocksMovement)
+            .with(Fighter { max_hp: 10, hp: 10, defense: 0, power: 1, xp: 0, on_death: DeathCallback::Monster })
+            .with(Ai::Basic)
+            .with(Actor::new(monster_speed))
+            .build();
+        let game = Game {
+            map: vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize],
+            messages: Messages::new(),
+            inventory: vec![],
+            dungeon_level: 1,
+            map_generator: MapGenerator::Rooms,
+            seed: 0,
+            rng: Pcg64::seed_from_u64(0),
+        };
+        let mut fov_map = Map::new(MAP_WIDTH, MAP_HEIGHT);
+        fov_map.clear(true, true);
+        fov_map.compute_fov(10, 10, 10, true, FovAlgorithm::Basic);
+        (fov_map, game, world)
+    }
+
+    /// How many times the monster has hit the player.
+    fn monster_turns(world: &World) -> i32 {
+        PLAYER_HP - world.fighters.get(PLAYER_ID).unwrap().hp
+    }
+
+    #[test]
+    fn double_speed_acts_twice_per_tick() {
+        let (fov_map, mut game, mut world) = setup(200);
+        tick(&fov_map, &mut game, &mut world);
+        assert_eq!(monster_turns(&world), 2);
+        tick(&fov_map, &mut game, &mut world);
+        assert_eq!(monster_turns(&world), 4);
+    }
+
+    #[test]
+    fn half_speed_acts_every_other_tick() {
+        let (fov_map, mut game, mut world) = setup(50);
+        let turns: Vec<i32> = (0..4)
+            .map(|_| {
+                tick(&fov_map, &mut game, &mut world);
+                monster_turns(&world)
+            })
+            .collect();
+        assert_eq!(turns, vec![0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn climbing_delays_the_next_turn() {
+        let (fov_map, mut game, mut world) = setup(NORMAL_SPEED);
+        spend_energy(PLAYER_ID, Action::Climb, &mut world);
+        run_until_player_ready(&fov_map, &mut game, &mut world);
+        assert_eq!(monster_turns(&world), 2);
+    }
+
+    #[test]
+    fn using_items_hurries_the_next_turn() {
+        let (fov_map, mut game, mut world) = setup(NORMAL_SPEED);
+        // two quick item uses and a move take the time of two moves
+        for action in [Action::UseItem, Action::UseItem, Action::Move] {
+            spend_energy(PLAYER_ID, action, &mut world);
+            run_until_player_ready(&fov_map, &mut game, &mut world);
+        }
+        assert_eq!(monster_turns(&world), 2);
+    }
+}
@@ -0,0 +1,107 @@
+use tcod::colors::{DARKER_ORANGE, DARKER_YELLOW, LIGHT_GREEN, RED};
+use crate::{Game, PLAYER_ID};
+use crate::backend::{Event, KeyCode};
+use crate::engine::{render, Ui};
+use crate::gamemap::{MAP_HEIGHT, MAP_WIDTH};
+use crate::keymap::Command;
+use crate::ecs::{Entity, World};
+use crate::movement::distance;
+use crate::ranged::fly;
+
+/// Lets the player pick a tile with the mouse or by moving a cursor with the movement keys and pressing Enter.
+/// Only tiles in field of view and within `max_range` of the player can be picked, every tile within `radius`
+/// of the cursor is highlighted. Returns `None` when cancelled with a right click or Escape.
+pub fn target_tile(ui: &mut Ui, game: &mut Game, world: &World, max_range: Option<f32>, radius: i32) -> Option<(i32, i32)> {
+    pick_tile(ui, game, world, max_range, radius, false)
+}
+
+/// Like `target_tile`, but shows the line a missile flies from the player to the cursor, up to where it stops.
+pub fn target_line(ui: &mut Ui, game: &mut Game, world: &World, max_range: Option<f32>) -> Option<(i32, i32)> {
+    pick_tile(ui, game, world, max_range, 0, true)
+}
+
+fn pick_tile(ui: &mut Ui, game: &mut Game, world: &World, max_range: Option<f32>, radius: i32, line: bool) -> Option<(i32, i32)> {
+    let mut cursor = world.player_position();
+    loop {
+        render(ui, game, world, false);
+        if line {
+            draw_missile_line(ui, game, world, cursor);
+        }
+        draw_target_area(ui, world, cursor, max_range, radius);
+        ui.renderer.flush();
+
+        ui.key = Default::default();
+        ui.mouse.left_clicked = false;
+        ui.mouse.right_clicked = false;
+        match ui.input.check_for_event() {
+            Some(Event::Mouse(m)) => {
+                ui.mouse = m;
+                if in_map(m.x, m.y) {
+                    cursor = (m.x, m.y);
+                }
+            }
+            Some(Event::Key(k)) => ui.key = k,
+            None => {}
+        }
+
+        let (dx, dy) = match ui.keymap.command(ui.key) {
+            Some(Command::Move(dx, dy)) => (dx, dy),
+            _ => (0, 0),
+        };
+        if in_map(cursor.0 + dx, cursor.1 + dy) {
+            cursor = (cursor.0 + dx, cursor.1 + dy);
+        }
+
+        let selected = ui.key.code == KeyCode::Enter || ui.mouse.left_clicked;
+        if selected && is_valid_target(ui, world, cursor, max_range) {
+            return Some(cursor);
+        }
+
+        if ui.mouse.right_clicked || ui.key.code == KeyCode::Escape {
+            return None;
+        }
+    }
+}
+
+/// Like `target_tile`, but only accepts a tile with a fighter other than the player on it.
+pub fn target_monster(ui: &mut Ui, game: &mut Game, world: &World, max_range: Option<f32>) -> Option<Entity> {
+    loop {
+        let (x, y) = target_tile(ui, game, world, max_range, 0)?;
+        let monster = world.entities_at(x, y).find(|&e| e != PLAYER_ID && world.fighters.contains(e));
+        if monster.is_some() {
+            return monster;
+        }
+    }
+}
+
+fn in_map(x: i32, y: i32) -> bool {
+    (0..MAP_WIDTH).contains(&x) && (0..MAP_HEIGHT).contains(&y)
+}
+
+fn is_valid_target(ui: &Ui, world: &World, (x, y): (i32, i32), max_range: Option<f32>) -> bool {
+    in_map(x, y)
+        && ui.fov.is_in_fov(x, y)
+        && max_range.is_none_or(|range| distance(world.player_position(), (x, y)) <= range)
+}
+
+fn draw_missile_line(ui: &mut Ui, game: &Game, world: &World, cursor: (i32, i32)) {
+    let (path, _) = fly(world.player_position(), cursor, &game.map, world);
+    for (x, y) in path {
+        ui.renderer.set_background(x, y, DARKER_YELLOW);
+    }
+}
+
+fn draw_target_area(ui: &mut Ui, world: &World, cursor: (i32, i32), max_range: Option<f32>, radius: i32) {
+    let (cx, cy) = cursor;
+    for y in (cy - radius)..=(cy + radius) {
+        for x in (cx - radius)..=(cx + radius) {
+            let in_radius = ((x - cx).pow(2) + (y - cy).pow(2)) as f32 <= (radius * radius) as f32;
+            if in_map(x, y) && in_radius {
+                ui.renderer.set_background(x, y, DARKER_ORANGE);
+            }
+        }
+    }
+
+    let cursor_color = if is_valid_target(ui, world, cursor, max_range) { LIGHT_GREEN } else { RED };
+    ui.renderer.set_background(cx, cy, cursor_color);
+}
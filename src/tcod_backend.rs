@@ -0,0 +1,143 @@
+use tcod::{BackgroundFlag, Color, Console};
+use tcod::console::Root;
+use tcod::input::{self, KeyCode as TcodKeyCode};
+use crate::backend::{Event, Input, Key, KeyCode, Mouse, Renderer};
+
+pub struct TcodRenderer {
+    root: Root,
+}
+
+impl TcodRenderer {
+    pub fn new(root: Root) -> Self {
+        Self { root }
+    }
+}
+
+impl Renderer for TcodRenderer {
+    fn size(&self) -> (i32, i32) {
+        (self.root.width(), self.root.height())
+    }
+
+    fn clear(&mut self) {
+        self.root.clear();
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: Color) {
+        self.root.set_char(x, y, glyph);
+        self.root.set_char_foreground(x, y, color);
+    }
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color) {
+        self.root.set_char_background(x, y, color, BackgroundFlag::Set);
+    }
+
+    fn flush(&mut self) {
+        self.root.flush();
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let fullscreen = self.root.is_fullscreen();
+        self.root.set_fullscreen(!fullscreen);
+    }
+
+    fn window_closed(&self) -> bool {
+        self.root.window_closed()
+    }
+}
+
+pub struct TcodInput;
+
+impl Input for TcodInput {
+    fn check_for_event(&mut self) -> Option<Event> {
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, input::Event::Key(key))) => Some(Event::Key(convert_key(key))),
+            Some((_, input::Event::Mouse(mouse))) => Some(Event::Mouse(convert_mouse(mouse))),
+            None => None,
+        }
+    }
+
+    fn wait_for_keypress(&mut self) -> Key {
+        loop {
+            if let Some((_, input::Event::Key(key))) = input::wait_for_event(input::KEY_PRESS, true) {
+                // skip the half of a printable key press that carries no character
+                let key = convert_key(key);
+                if key.code != KeyCode::None {
+                    return key;
+                }
+            }
+        }
+    }
+}
+
+fn convert_key(key: input::Key) -> Key {
+    let code = match key.code {
+        TcodKeyCode::Text => key.text().chars().next().map_or(KeyCode::None, KeyCode::Char),
+        // SDL sends no text for Ctrl and Alt combinations, so those only come through here
+        TcodKeyCode::Char if key.ctrl || key.alt => KeyCode::Char(key.printable),
+        // a plain press also arrives as a `Text` event, which has the character with shift applied
+        TcodKeyCode::Char => KeyCode::None,
+        TcodKeyCode::Up => KeyCode::Up,
+        TcodKeyCode::Down => KeyCode::Down,
+        TcodKeyCode::Left => KeyCode::Left,
+        TcodKeyCode::Right => KeyCode::Right,
+        TcodKeyCode::Home => KeyCode::Home,
+        TcodKeyCode::End => KeyCode::End,
+        TcodKeyCode::PageUp => KeyCode::PageUp,
+        TcodKeyCode::PageDown => KeyCode::PageDown,
+        TcodKeyCode::NumPad0 => KeyCode::NumPad(0),
+        TcodKeyCode::NumPad1 => KeyCode::NumPad(1),
+        TcodKeyCode::NumPad2 => KeyCode::NumPad(2),
+        TcodKeyCode::NumPad3 => KeyCode::NumPad(3),
+        TcodKeyCode::NumPad4 => KeyCode::NumPad(4),
+        TcodKeyCode::NumPad5 => KeyCode::NumPad(5),
+        TcodKeyCode::NumPad6 => KeyCode::NumPad(6),
+        TcodKeyCode::NumPad7 => KeyCode::NumPad(7),
+        TcodKeyCode::NumPad8 => KeyCode::NumPad(8),
+        TcodKeyCode::NumPad9 => KeyCode::NumPad(9),
+        TcodKeyCode::Enter => KeyCode::Enter,
+        TcodKeyCode::Escape => KeyCode::Escape,
+        TcodKeyCode::Backspace => KeyCode::Backspace,
+        TcodKeyCode::Spacebar => KeyCode::Spacebar,
+        _ => KeyCode::None,
+    };
+    Key { code, alt: key.alt, ctrl: key.ctrl, shift: key.shift }
+}
+
+fn convert_mouse(mouse: input::Mouse) -> Mouse {
+    Mouse {
+        x: mouse.cx as i32,
+        y: mouse.cy as i32,
+        left_clicked: mouse.lbutton_pressed,
+        right_clicked: mouse.rbutton_pressed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcod_key(code: TcodKeyCode, printable: char) -> input::Key {
+        input::Key { code, printable, pressed: true, ..Default::default() }
+    }
+
+    #[test]
+    fn keeps_ctrl_and_alt_letters() {
+        let ctrl_s = convert_key(input::Key { ctrl: true, ..tcod_key(TcodKeyCode::Char, 's') });
+        assert_eq!(ctrl_s, Key { ctrl: true, ..Key::char('s') });
+        let alt_x = convert_key(input::Key { alt: true, ..tcod_key(TcodKeyCode::Char, 'x') });
+        assert_eq!(alt_x, Key { alt: true, ..Key::char('x') });
+    }
+
+    #[test]
+    fn drops_plain_letters_that_come_again_as_text() {
+        assert_eq!(convert_key(tcod_key(TcodKeyCode::Char, 'k')).code, KeyCode::None);
+        let shifted = convert_key(input::Key { shift: true, ..tcod_key(TcodKeyCode::Char, 'd') });
+        assert_eq!(shifted.code, KeyCode::None);
+    }
+
+    #[test]
+    fn converts_special_keys() {
+        assert_eq!(convert_key(tcod_key(TcodKeyCode::Up, '\0')), Key::new(KeyCode::Up));
+        assert_eq!(convert_key(tcod_key(TcodKeyCode::NumPad7, '\0')), Key::new(KeyCode::NumPad(7)));
+    }
+}
@@ -0,0 +1,69 @@
+//! What the terrain does to the creatures crossing it.
+
+use rand::Rng;
+use tcod::colors::{LIGHT_BLUE, ORANGE};
+use crate::{Game, PLAYER_ID};
+use crate::combat::take_damage;
+use crate::components::Locomotion;
+use crate::ecs::{Entity, World};
+use crate::gamemap::TileKind;
+use crate::inventory::take_one;
+
+pub const LAVA_DAMAGE: i32 = 6;
+/// Damage taken when falling down a chasm to the next level.
+pub const FALL_DAMAGE: i32 = 3;
+/// Percentage chance to lose a carried item with every swim through deep water.
+const WATER_LOSS_CHANCE: u32 = 20;
+
+pub fn locomotion(entity: Entity, world: &World) -> Locomotion {
+    world.get::<Locomotion>(entity).copied().unwrap_or_default()
+}
+
+/// Whether the creature moves onto the terrain. The player may jump into anything that is not solid,
+/// monsters keep out of what they cannot cross.
+pub fn can_enter(entity: Entity, kind: TileKind, world: &World) -> bool {
+    use Locomotion::*;
+    use TileKind::*;
+    let locomotion = locomotion(entity, world);
+    match kind {
+        Wall | ClosedDoor | SecretDoor => false,
+        Floor | OpenDoor | Rubble => true,
+        DeepWater => entity == PLAYER_ID || locomotion == Swim || locomotion == Fly,
+        Lava | Chasm => entity == PLAYER_ID || locomotion == Fly,
+    }
+}
+
+/// Applies the terrain the entity just moved onto. Falling down a chasm is left to the caller, it changes levels.
+pub fn enter_terrain(entity: Entity, game: &mut Game, world: &mut World) {
+    let (x, y) = match world.position(entity) {
+        Some(position) => position,
+        None => return,
+    };
+    if locomotion(entity, world) == Locomotion::Fly {
+        return;
+    }
+    match game.map[x as usize][y as usize].kind {
+        TileKind::Lava => {
+            game.messages.add(format!("{} is burned by the lava for {} hit points.", world.name(entity), LAVA_DAMAGE), ORANGE);
+            take_damage(entity, LAVA_DAMAGE, game, world);
+        }
+        TileKind::DeepWater if entity == PLAYER_ID && locomotion(entity, world) != Locomotion::Swim => {
+            lose_item_in_water(game, world);
+        }
+        _ => {}
+    }
+}
+
+/// Maybe one of the player's items that are not worn slips away while swimming.
+fn lose_item_in_water(game: &mut Game, world: &mut World) {
+    let loose: Vec<usize> = (0..game.inventory.len())
+        .filter(|&index| world.equipment.get(game.inventory[index]).is_none_or(|e| !e.equipped))
+        .collect();
+    if loose.is_empty() || game.rng.gen_range(0..100) >= WATER_LOSS_CHANCE {
+        return;
+    }
+    let index = loose[game.rng.gen_range(0..loose.len())];
+    let item = take_one(index, game, world);
+    game.messages.add(format!("Your {} slips away into the deep water!", world.name(item)), LIGHT_BLUE);
+    world.despawn(item);
+}
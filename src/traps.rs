@@ -0,0 +1,195 @@
+//! Traps: hidden until searched for or stepped on, then avoided by monsters and disarmable.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::{LIGHT_CYAN, ORANGE, RED, WHITE, YELLOW};
+use tcod::Color;
+use crate::{Game, PLAYER_ID};
+use crate::ai::Ai;
+use crate::combat::take_damage;
+use crate::components::{Locomotion, Name, Position, Renderable};
+use crate::ecs::{Entity, World};
+use crate::gamemap::{is_blocked, MAP_HEIGHT, MAP_WIDTH};
+use crate::movement::distance;
+use crate::scheduler::ACTION_ENERGY;
+use crate::terrain::locomotion;
+
+const DART_DAMAGE: i32 = 4;
+const PIT_DAMAGE: i32 = 2;
+/// Monsters this close to an alarm come to see what set it off.
+const ALARM_RADIUS: f32 = 20.0;
+/// Turns an alarmed monster searches before it gives up.
+const ALARM_TURNS: i32 = 20;
+/// Percentage chance that searching finds a hidden trap next to the player.
+const DETECT_CHANCE: u32 = 35;
+const DISARM_CHANCE: u32 = 70;
+/// Percentage chance that a failed disarm sets the trap off.
+const DISARM_TRIGGER_CHANCE: u32 = 50;
+/// Random spots tried for a teleport landing.
+const TELEPORT_TRIES: i32 = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// Hidden traps are not drawn and monsters do not know about them either.
+    pub hidden: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TrapKind {
+    Dart,
+    Teleport,
+    /// Calls the monsters around to the trap.
+    Alarm,
+    /// Hurts and takes a turn to climb out of.
+    Pit,
+}
+
+impl TrapKind {
+    fn name(self) -> &'static str {
+        match self {
+            TrapKind::Dart => "dart trap",
+            TrapKind::Teleport => "teleport trap",
+            TrapKind::Alarm => "alarm trap",
+            TrapKind::Pit => "pit",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            TrapKind::Dart => RED,
+            TrapKind::Teleport => LIGHT_CYAN,
+            TrapKind::Alarm => YELLOW,
+            TrapKind::Pit => ORANGE,
+        }
+    }
+}
+
+pub fn spawn_trap(kind: TrapKind, x: i32, y: i32, world: &mut World) -> Entity {
+    world.spawn()
+        .with(Position::new(x, y))
+        .with(Renderable::new('^', kind.color()))
+        .with(Name::new(kind.name()))
+        .with(Trap { kind, hidden: true })
+        .build()
+}
+
+/// Hidden traps are left out of everything the player sees.
+pub fn is_hidden(entity: Entity, world: &World) -> bool {
+    world.traps.get(entity).is_some_and(|trap| trap.hidden)
+}
+
+/// Traps found so far on a tile, monsters keep off them.
+pub fn known_trap_at(x: i32, y: i32, world: &World) -> Option<Entity> {
+    world.entities_at(x, y).find(|&e| world.traps.get(e).is_some_and(|trap| !trap.hidden))
+}
+
+/// Sets off a trap on the tile the entity just moved onto. Fliers pass over them.
+pub fn trigger_traps(entity: Entity, game: &mut Game, world: &mut World) {
+    let (x, y) = match world.position(entity) {
+        Some(position) => position,
+        None => return,
+    };
+    if locomotion(entity, world) == Locomotion::Fly {
+        return;
+    }
+    let trap = world.entities_at(x, y).find(|&e| world.traps.contains(e));
+    if let Some(trap) = trap {
+        spring(trap, entity, game, world);
+    }
+}
+
+/// The trap goes off on the victim and is no longer hidden.
+fn spring(trap: Entity, victim: Entity, game: &mut Game, world: &mut World) {
+    let kind = match world.traps.get_mut(trap) {
+        Some(trap) => {
+            trap.hidden = false;
+            trap.kind
+        }
+        None => return,
+    };
+    let name = world.name(victim).to_string();
+    match kind {
+        TrapKind::Dart => {
+            game.messages.add(format!("A dart shoots out at {} for {} hit points!", name, DART_DAMAGE), RED);
+            take_damage(victim, DART_DAMAGE, game, world);
+        }
+        TrapKind::Teleport => {
+            game.messages.add(format!("{} is caught in a flash of light!", name), LIGHT_CYAN);
+            teleport(victim, game, world);
+        }
+        TrapKind::Alarm => {
+            game.messages.add("A loud alarm goes off!", YELLOW);
+            if let Some(position) = world.position(trap) {
+                sound_alarm(position, world);
+            }
+        }
+        TrapKind::Pit => {
+            game.messages.add(format!("{} falls into a pit for {} hit points!", name, PIT_DAMAGE), ORANGE);
+            take_damage(victim, PIT_DAMAGE, game, world);
+            // climbing out takes a turn
+            if let Some(actor) = world.actors.get_mut(victim) {
+                actor.energy -= ACTION_ENERGY;
+            }
+        }
+    }
+}
+
+fn teleport(entity: Entity, game: &mut Game, world: &mut World) {
+    for _ in 0..TELEPORT_TRIES {
+        let x = game.rng.gen_range(0..MAP_WIDTH);
+        let y = game.rng.gen_range(0..MAP_HEIGHT);
+        if !is_blocked(x, y, entity, &game.map, world) && game.map[x as usize][y as usize].kind.walkable() {
+            world.set_position(entity, x, y);
+            return;
+        }
+    }
+}
+
+/// Sends every monster in earshot to the alarm, those already heading for another one go to the new one.
+fn sound_alarm((x, y): (i32, i32), world: &mut World) {
+    let monsters: Vec<(Entity, Ai)> = world.ais.iter()
+        .filter(|&(entity, _)| world.position(entity).is_some_and(|position| distance(position, (x, y)) <= ALARM_RADIUS))
+        .map(|(entity, ai)| (entity, ai.clone()))
+        .collect();
+    for (monster, ai) in monsters {
+        let previous_ai = match ai {
+            Ai::Alerted { previous_ai, .. } => previous_ai,
+            ai => Box::new(ai),
+        };
+        world.insert(monster, Ai::Alerted { x, y, num_turns: ALARM_TURNS, previous_ai });
+    }
+}
+
+/// Finds each hidden trap next to the tile with a chance. Returns how many were found.
+pub fn detect_traps(x: i32, y: i32, game: &mut Game, world: &mut World) -> usize {
+    let hidden: Vec<Entity> = world.traps.iter()
+        .filter(|(_, trap)| trap.hidden)
+        .map(|(entity, _)| entity)
+        .filter(|&entity| world.position(entity).is_some_and(|(tx, ty)| (tx - x).abs() <= 1 && (ty - y).abs() <= 1))
+        .collect();
+    let mut found = 0;
+    for trap in hidden {
+        if game.rng.gen_range(0..100) < DETECT_CHANCE {
+            if let Some(trap) = world.traps.get_mut(trap) {
+                trap.hidden = false;
+            }
+            found += 1;
+        }
+    }
+    found
+}
+
+/// Tries to take the trap apart, a failure may set it off on the player.
+pub fn disarm(trap: Entity, game: &mut Game, world: &mut World) {
+    let name = world.name(trap).to_string();
+    if game.rng.gen_range(0..100) < DISARM_CHANCE {
+        game.messages.add(format!("You disarm the {}.", name), WHITE);
+        world.despawn(trap);
+    } else if game.rng.gen_range(0..100) < DISARM_TRIGGER_CHANCE {
+        game.messages.add(format!("You set off the {}!", name), RED);
+        spring(trap, PLAYER_ID, game, world);
+    } else {
+        game.messages.add(format!("You fail to disarm the {}.", name), WHITE);
+    }
+}
@@ -0,0 +1,126 @@
+//! Stamping the hand-made vaults from `data/vaults.toml` into generated levels.
+
+use std::collections::{HashMap, VecDeque};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_pcg::Pcg64;
+use crate::content::{is_outside_vault, Content, VaultTemplate, VAULT_DOOR, VAULT_OUTSIDE, VAULT_SECRET_DOOR, VAULT_WALL};
+use crate::ecs::World;
+use crate::gamemap::{from_dungeon_level, GameMap, Tile, TileKind, MAP_HEIGHT, MAP_WIDTH};
+
+/// Percentage of levels that get a vault.
+const VAULT_CHANCE: u32 = 30;
+/// Random spots tried before a level goes without its vault.
+const VAULT_PLACEMENT_TRIES: i32 = 50;
+
+/// Maybe stamps a vault into solid rock of the level and tunnels its doors to the nearest floor.
+pub fn place_vault(map: &mut GameMap, world: &mut World, level: u32, content: &Content, rng: &mut Pcg64) {
+    if rng.gen_range(0..100) >= VAULT_CHANCE {
+        return;
+    }
+    let weights = content.vaults.iter().map(|vault| from_dungeon_level(&vault.spawn, level));
+    let choice = match WeightedIndex::new(weights) {
+        Ok(choice) => choice,
+        // no vault appears this deep
+        Err(_) => return,
+    };
+    let vault = &content.vaults[choice.sample(rng)];
+    let rows = oriented_rows(vault, rng);
+    let (w, h) = (rows[0].len() as i32, rows.len() as i32);
+
+    for _ in 0..VAULT_PLACEMENT_TRIES {
+        // keep a tile of rock around the vault and room for the tunnels from its doors inside the map edge
+        let x = rng.gen_range(2..(MAP_WIDTH - 1 - w));
+        let y = rng.gen_range(2..(MAP_HEIGHT - 1 - h));
+        let solid_rock = (x - 1..=x + w)
+            .all(|mx| (y - 1..=y + h).all(|my| map[mx as usize][my as usize].kind == TileKind::Wall));
+        if solid_rock {
+            stamp(vault, &rows, x, y, map, world, content);
+            return;
+        }
+    }
+}
+
+/// The vault's rows, randomly mirrored and turned where the vault allows it.
+fn oriented_rows(vault: &VaultTemplate, rng: &mut Pcg64) -> Vec<Vec<char>> {
+    let mut rows = vault.rows();
+    if vault.mirror && rng.gen() {
+        for row in &mut rows {
+            row.reverse();
+        }
+    }
+    if vault.rotate {
+        for _ in 0..rng.gen_range(0..4) {
+            // a quarter turn clockwise
+            rows = (0..rows[0].len())
+                .map(|column| rows.iter().rev().map(|row| row[column]).collect())
+                .collect();
+        }
+    }
+    rows
+}
+
+fn stamp(vault: &VaultTemplate, rows: &[Vec<char>], x: i32, y: i32, map: &mut GameMap, world: &mut World, content: &Content) {
+    let mut doors = vec![];
+    for (row_y, row) in rows.iter().enumerate() {
+        for (row_x, &symbol) in row.iter().enumerate() {
+            let (mx, my) = (x + row_x as i32, y + row_y as i32);
+            match symbol {
+                VAULT_OUTSIDE => continue,
+                VAULT_WALL => {}
+                VAULT_DOOR => map[mx as usize][my as usize] = Tile::new(TileKind::ClosedDoor),
+                VAULT_SECRET_DOOR => map[mx as usize][my as usize] = Tile::new(TileKind::SecretDoor),
+                _ => map[mx as usize][my as usize] = Tile::empty(),
+            }
+            if symbol == VAULT_DOOR || symbol == VAULT_SECRET_DOOR {
+                let (rx, ry) = (row_x as i32, row_y as i32);
+                let exit = [(rx - 1, ry), (rx + 1, ry), (rx, ry - 1), (rx, ry + 1)].into_iter()
+                    .find(|&(ex, ey)| is_outside_vault(rows, ex, ey))
+                    .expect("vault doors are checked to lead out when loading");
+                doors.push((x + exit.0, y + exit.1));
+            }
+            if let Some(name) = vault.legend.get(&symbol) {
+                content.spawn(name, mx, my, world);
+            }
+        }
+    }
+
+    let in_vault = |mx: i32, my: i32| {
+        let (rx, ry) = (mx - x, my - y);
+        !is_outside_vault(rows, rx, ry)
+    };
+    for exit in doors {
+        tunnel_to_floor(exit, &in_vault, map);
+    }
+}
+
+/// Digs the shortest way through rock from `from` to the nearest floor around the vault.
+fn tunnel_to_floor(from: (i32, i32), in_vault: &dyn Fn(i32, i32) -> bool, map: &mut GameMap) {
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    came_from.insert(from, from);
+    while let Some((x, y)) = queue.pop_front() {
+        if !map[x as usize][y as usize].blocked() {
+            let mut current = (x, y);
+            while current != from {
+                current = came_from[&current];
+                map[current.0 as usize][current.1 as usize] = Tile::empty();
+            }
+            return;
+        }
+        for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            // the map edge stays rock
+            let inside_map = next.0 > 0 && next.1 > 0 && next.0 < MAP_WIDTH - 1 && next.1 < MAP_HEIGHT - 1;
+            if inside_map && !in_vault(next.0, next.1) && !came_from.contains_key(&next) && diggable(next, map) {
+                came_from.insert(next, (x, y));
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Rock to dig through, or the floor the tunnel ends on.
+fn diggable((x, y): (i32, i32), map: &GameMap) -> bool {
+    let tile = map[x as usize][y as usize];
+    tile.kind == TileKind::Wall || !tile.blocked()
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use roguelike::backend::{Event, Key, KeyCode};
+use roguelike::components::Position;
+use roguelike::content::Content;
+use roguelike::engine::{main_menu, new_game, play_game, Ui};
+use roguelike::gamemap::{MapGenerator, TileKind};
+use roguelike::gui::PANEL_Y;
+use roguelike::headless::{HeadlessInput, HeadlessRenderer};
+use roguelike::keymap::Keymap;
+use roguelike::save::load_game;
+use roguelike::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[test]
+fn renders_the_player_and_the_seed() {
+    let mut renderer = HeadlessRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut input = HeadlessInput::new([Event::Key(Key::char('.'))]);
+    let mut ui = Ui::new(&mut renderer, &mut input, Keymap::default(), Content::default());
+    let (mut game, mut world) = new_game(&mut ui, 42, MapGenerator::Rooms);
+    let start = world.player_position();
+
+    play_game(&mut ui, &mut game, &mut world);
+
+    assert_eq!(world.player_position(), start);
+    assert_eq!(renderer.cell(start.0, start.1).glyph, '@');
+    assert!(renderer.row(PANEL_Y + 5).contains("Seed: 42"));
+}
+
+#[test]
+fn quitting_saves_to_the_given_file_and_ends_the_menu() {
+    let save_file = env::temp_dir().join(format!("roguelike-test-{}", std::process::id()));
+    let mut renderer = HeadlessRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    // a new game from the main menu, then save and quit
+    let mut input = HeadlessInput::new([Event::Key(Key::char('a')), Event::Key(Key::new(KeyCode::Escape))]);
+    let mut ui = Ui::new(&mut renderer, &mut input, Keymap::default(), Content::default());
+    ui.save_file = save_file.to_string_lossy().into();
+
+    main_menu(&mut ui, Some(7), MapGenerator::Rooms);
+
+    let (game, _) = load_game(&ui.save_file).expect("the game was not saved");
+    assert_eq!(game.seed, 7);
+    fs::remove_file(&save_file).unwrap();
+}
+
+/// The tiles of a new game's first level, and where everything on it stands.
+type Level = (Vec<Vec<TileKind>>, Vec<(Position, String, char)>);
+
+fn generate(seed: u64) -> Level {
+    let mut renderer = HeadlessRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut input = HeadlessInput::new([]);
+    let mut ui = Ui::new(&mut renderer, &mut input, Keymap::default(), Content::default());
+    let (game, world) = new_game(&mut ui, seed, MapGenerator::Rooms);
+    let tiles = game.map.iter().map(|column| column.iter().map(|tile| tile.kind).collect()).collect();
+    let entities = world.positions.iter()
+        .map(|(entity, &position)| (position, world.name(entity).to_string(), world.renderables.get(entity).map_or(' ', |r| r.glyph)))
+        .collect();
+    (tiles, entities)
+}
+
+#[test]
+fn the_same_seed_makes_the_same_level() {
+    let (tiles, entities) = generate(1234);
+    assert_eq!(generate(1234), (tiles.clone(), entities.clone()));
+    assert!(entities.len() > 1);
+
+    let (other_tiles, _) = generate(4321);
+    assert_ne!(other_tiles, tiles);
+}